@@ -0,0 +1,352 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, DynamicImage, Frame, RgbImage};
+use serde::Serialize;
+
+use crate::error::{Result, ThumbsdownError};
+
+pub const DEFAULT_DELAY_MS: u32 = 500;
+pub const DEFAULT_WEBP_QUALITY: u8 = 80;
+
+// NeuQuant sampling factor 1-30; 10 is the usual speed/quality trade-off
+const GIF_SPEED: i32 = 10;
+// Browsers stretch shorter GIF delays to 100 ms
+const MIN_BURST_DELAY_MS: u32 = 20;
+const WEBP_ANIMATION_FLAG: u8 = 0x02;
+// ANMF flags: overwrite the canvas instead of alpha-blending, no disposal
+const WEBP_NO_BLEND: u8 = 0x02;
+
+/// Container of an animated preview.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnimationFormat {
+    Gif,
+    Webp,
+    /// Animated PNG
+    Apng,
+}
+
+impl AnimationFormat {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "gif" => Some(Self::Gif),
+            "webp" => Some(Self::Webp),
+            "png" | "apng" => Some(Self::Apng),
+            _ => None,
+        }
+    }
+
+    /// The format for `path`, failing when the extension names none.
+    pub fn resolve(path: &Path) -> Result<Self> {
+        Self::from_path(path)
+            .ok_or_else(|| ThumbsdownError::UnsupportedAnimationFormat(path.to_path_buf()))
+    }
+}
+
+/// Timing and encoder settings of an animated preview.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct AnimationOptions {
+    /// How long each sample point stays on screen
+    pub delay_ms: u32,
+    /// Number of times the animation plays; 0 loops forever
+    pub loops: u32,
+    /// Consecutive frames shown at each sample point, starting with the
+    /// thumbnail
+    pub burst: u32,
+    /// Quality 1-100 for lossy WebP
+    pub quality: Option<u8>,
+    /// Write lossless WebP
+    pub lossless: bool,
+}
+
+impl Default for AnimationOptions {
+    fn default() -> Self {
+        Self {
+            delay_ms: DEFAULT_DELAY_MS,
+            loops: 0,
+            burst: 1,
+            quality: None,
+            lossless: false,
+        }
+    }
+}
+
+impl AnimationOptions {
+    /// Delays for the `burst` frames of one sample point: the video's frame
+    /// interval between frames of the burst, `delay_ms` on the last one.
+    pub fn burst_delays(&self, fps: f64) -> Vec<u32> {
+        let burst = self.burst.max(1) as usize;
+        let frame_ms = if fps > 0.0 {
+            (1000.0 / fps).round() as u32
+        } else {
+            self.delay_ms
+        };
+        let mut delays = vec![frame_ms.max(MIN_BURST_DELAY_MS); burst];
+        delays[burst - 1] = self.delay_ms;
+        delays
+    }
+}
+
+/// A single frame of an animated preview.
+#[derive(Debug, Clone)]
+pub struct AnimationFrame {
+    pub image: RgbImage,
+    /// Time the frame stays on screen
+    pub delay_ms: u32,
+}
+
+/// Writes `frames` as an animation in the format named by `path`'s
+/// extension. Every frame must have the size of the first.
+pub fn save(frames: &[AnimationFrame], path: &Path, options: &AnimationOptions) -> Result<()> {
+    let format = AnimationFormat::resolve(path)?;
+    let Some(first) = frames.first() else {
+        return Err(ThumbsdownError::Encode(
+            "an animation needs at least one frame".to_string(),
+        ));
+    };
+    let (width, height) = first.image.dimensions();
+    if let Some(frame) = frames
+        .iter()
+        .find(|f| f.image.dimensions() != (width, height))
+    {
+        return Err(ThumbsdownError::Encode(format!(
+            "animation frame is {}x{}, expected {width}x{height}",
+            frame.image.width(),
+            frame.image.height()
+        )));
+    }
+
+    match format {
+        AnimationFormat::Gif => save_gif(frames, path, options.loops),
+        AnimationFormat::Webp => {
+            let data = encode_webp(frames, options)?;
+            std::fs::write(path, data)?;
+            Ok(())
+        }
+        AnimationFormat::Apng => save_apng(frames, path, options.loops),
+    }
+}
+
+fn save_gif(frames: &[AnimationFrame], path: &Path, loops: u32) -> Result<()> {
+    let writer = BufWriter::new(File::create(path)?);
+    let mut encoder = GifEncoder::new_with_speed(writer, GIF_SPEED);
+    // The GIF loop count is the number of repeats after the first play
+    let repeat = match loops {
+        0 => Repeat::Infinite,
+        n => Repeat::Finite(u16::try_from(n - 1).unwrap_or(u16::MAX)),
+    };
+    encoder.set_repeat(repeat)?;
+    encoder.encode_frames(frames.iter().map(|frame| {
+        let image = DynamicImage::ImageRgb8(frame.image.clone()).into_rgba8();
+        Frame::from_parts(image, 0, 0, Delay::from_numer_denom_ms(frame.delay_ms, 1))
+    }))?;
+    Ok(())
+}
+
+/// Builds an animated WebP from frames encoded as still images. libwebp's
+/// animation encoder derives the last frame's duration from the others, so
+/// the container is written directly: a VP8X header with the animation
+/// flag, the ANIM loop settings, then one full-canvas ANMF chunk per frame.
+fn encode_webp(frames: &[AnimationFrame], options: &AnimationOptions) -> Result<Vec<u8>> {
+    let (width, height) = frames[0].image.dimensions();
+    let quality = options
+        .quality
+        .unwrap_or(DEFAULT_WEBP_QUALITY)
+        .clamp(1, 100);
+    let mut body = Vec::new();
+
+    let mut vp8x = [0u8; 10];
+    vp8x[0] = WEBP_ANIMATION_FLAG;
+    vp8x[4..7].copy_from_slice(&u24(width - 1));
+    vp8x[7..10].copy_from_slice(&u24(height - 1));
+    write_chunk(&mut body, b"VP8X", &vp8x);
+
+    // Background colour (a hint players may ignore) then the loop count
+    let mut anim = [0xFFu8; 6];
+    anim[4..6].copy_from_slice(
+        &u16::try_from(options.loops)
+            .unwrap_or(u16::MAX)
+            .to_le_bytes(),
+    );
+    write_chunk(&mut body, b"ANIM", &anim);
+
+    for frame in frames {
+        let still = webp::Encoder::from_rgb(&frame.image, width, height)
+            .encode_simple(options.lossless, quality as f32)
+            .map_err(|e| ThumbsdownError::Encode(format!("webp: {e:?}")))?;
+        // Keep the image chunks, dropping the RIFF and WEBP headers
+        let image_chunks = still.get(12..).unwrap_or_default();
+
+        let mut anmf = Vec::with_capacity(16 + image_chunks.len());
+        anmf.extend_from_slice(&[0; 6]); // frame offset 0,0
+        anmf.extend_from_slice(&u24(width - 1));
+        anmf.extend_from_slice(&u24(height - 1));
+        anmf.extend_from_slice(&u24(frame.delay_ms));
+        anmf.push(WEBP_NO_BLEND);
+        anmf.extend_from_slice(image_chunks);
+        write_chunk(&mut body, b"ANMF", &anmf);
+    }
+
+    let riff_size = u32::try_from(body.len() + 4)
+        .map_err(|_| ThumbsdownError::Encode("webp: animation exceeds 4 GiB".to_string()))?;
+    let mut data = Vec::with_capacity(body.len() + 12);
+    data.extend_from_slice(b"RIFF");
+    data.extend_from_slice(&riff_size.to_le_bytes());
+    data.extend_from_slice(b"WEBP");
+    data.extend_from_slice(&body);
+    Ok(data)
+}
+
+/// Appends a RIFF chunk, padded to an even length.
+fn write_chunk(out: &mut Vec<u8>, fourcc: &[u8; 4], payload: &[u8]) {
+    out.extend_from_slice(fourcc);
+    out.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    out.extend_from_slice(payload);
+    if payload.len() % 2 == 1 {
+        out.push(0);
+    }
+}
+
+/// Little-endian 24-bit field, saturating at its maximum.
+fn u24(value: u32) -> [u8; 3] {
+    let [a, b, c, _] = value.min(0xFF_FFFF).to_le_bytes();
+    [a, b, c]
+}
+
+fn save_apng(frames: &[AnimationFrame], path: &Path, loops: u32) -> Result<()> {
+    let png_error = |e: png::EncodingError| ThumbsdownError::Encode(format!("png: {e}"));
+    let (width, height) = frames[0].image.dimensions();
+    let count = u32::try_from(frames.len())
+        .map_err(|_| ThumbsdownError::Encode("too many animation frames".to_string()))?;
+
+    let mut encoder = png::Encoder::new(BufWriter::new(File::create(path)?), width, height);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_animated(count, loops).map_err(png_error)?;
+    let mut writer = encoder.write_header().map_err(png_error)?;
+    for frame in frames {
+        let delay = u16::try_from(frame.delay_ms).unwrap_or(u16::MAX);
+        writer.set_frame_delay(delay, 1000).map_err(png_error)?;
+        writer.write_image_data(&frame.image).map_err(png_error)?;
+    }
+    writer.finish().map_err(png_error)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufReader;
+
+    use image::codecs::gif::GifDecoder;
+    use image::codecs::png::PngDecoder;
+    use image::codecs::webp::WebPDecoder;
+    use image::{AnimationDecoder, Rgb};
+
+    use super::*;
+
+    fn frames() -> Vec<AnimationFrame> {
+        [Rgb([255, 0, 0]), Rgb([0, 255, 0]), Rgb([0, 0, 255])]
+            .into_iter()
+            .zip([100, 200, 300])
+            .map(|(color, delay_ms)| AnimationFrame {
+                image: RgbImage::from_pixel(16, 8, color),
+                delay_ms,
+            })
+            .collect()
+    }
+
+    fn delays_ms(frames: Vec<Frame>) -> Vec<u32> {
+        frames
+            .iter()
+            .map(|frame| {
+                let (numer, denom) = frame.delay().numer_denom_ms();
+                numer / denom
+            })
+            .collect()
+    }
+
+    #[test]
+    fn infers_format_from_extension() {
+        assert_eq!(
+            AnimationFormat::from_path(Path::new("a.GIF")),
+            Some(AnimationFormat::Gif)
+        );
+        assert_eq!(
+            AnimationFormat::from_path(Path::new("a.apng")),
+            Some(AnimationFormat::Apng)
+        );
+        assert!(matches!(
+            AnimationFormat::resolve(Path::new("a.mp4")),
+            Err(ThumbsdownError::UnsupportedAnimationFormat(_))
+        ));
+    }
+
+    #[test]
+    fn burst_delays_hold_the_last_frame() {
+        let options = AnimationOptions {
+            burst: 3,
+            ..Default::default()
+        };
+        assert_eq!(options.burst_delays(25.0), vec![40, 40, DEFAULT_DELAY_MS]);
+        assert_eq!(options.burst_delays(120.0), vec![20, 20, DEFAULT_DELAY_MS]);
+        assert_eq!(
+            AnimationOptions::default().burst_delays(25.0),
+            vec![DEFAULT_DELAY_MS]
+        );
+    }
+
+    #[test]
+    fn gif_keeps_frames_and_delays() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("preview.gif");
+        save(&frames(), &path, &AnimationOptions::default()).expect("save");
+
+        let decoder =
+            GifDecoder::new(BufReader::new(File::open(&path).expect("open"))).expect("decoder");
+        let decoded = decoder.into_frames().collect_frames().expect("frames");
+        assert_eq!(delays_ms(decoded), vec![100, 200, 300]);
+    }
+
+    #[test]
+    fn apng_keeps_frames_and_delays() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("preview.png");
+        save(&frames(), &path, &AnimationOptions::default()).expect("save");
+
+        let decoder =
+            PngDecoder::new(BufReader::new(File::open(&path).expect("open"))).expect("decoder");
+        assert!(decoder.is_apng().expect("apng"));
+        let decoded = decoder
+            .apng()
+            .expect("apng")
+            .into_frames()
+            .collect_frames()
+            .expect("frames");
+        assert_eq!(delays_ms(decoded), vec![100, 200, 300]);
+    }
+
+    #[test]
+    fn webp_keeps_frames_and_delays() {
+        let data = encode_webp(&frames(), &AnimationOptions::default()).expect("encode");
+        let decoder = WebPDecoder::new(std::io::Cursor::new(data)).expect("decoder");
+        assert!(decoder.has_animation());
+        let decoded = decoder.into_frames().collect_frames().expect("frames");
+        assert_eq!(delays_ms(decoded), vec![100, 200, 300]);
+    }
+
+    #[test]
+    fn save_rejects_mismatched_frames() {
+        let mut frames = frames();
+        frames[1].image = RgbImage::new(4, 4);
+        let err = save(
+            &frames,
+            Path::new("preview.gif"),
+            &AnimationOptions::default(),
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("expected 16x8"));
+    }
+}
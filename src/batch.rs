@@ -0,0 +1,263 @@
+use std::path::{Path, PathBuf};
+
+use crate::error::{Result, ThumbsdownError};
+
+/// Extensions picked up when expanding directory inputs.
+pub const DEFAULT_EXTENSIONS: &[&str] = &[
+    "mp4", "m4v", "mkv", "mov", "avi", "webm", "wmv", "flv", "mpg", "mpeg", "ts",
+];
+
+/// Expands the given inputs into a list of video files. Files are taken as
+/// given (even if missing, so they can be reported per file); directories
+/// contribute the files matching `extensions`, descending into
+/// subdirectories when `recursive` is set. Directory contents are sorted.
+pub fn collect_inputs(
+    paths: &[PathBuf],
+    recursive: bool,
+    extensions: &[String],
+) -> Result<Vec<PathBuf>> {
+    let mut inputs = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            walk(path, recursive, extensions, &mut inputs)?;
+        } else {
+            inputs.push(path.clone());
+        }
+    }
+    Ok(inputs)
+}
+
+fn walk(
+    dir: &Path,
+    recursive: bool,
+    extensions: &[String],
+    inputs: &mut Vec<PathBuf>,
+) -> Result<()> {
+    let mut entries: Vec<PathBuf> = std::fs::read_dir(dir)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<std::io::Result<_>>()?;
+    entries.sort();
+
+    for path in entries {
+        if path.is_dir() {
+            if recursive {
+                walk(&path, recursive, extensions, inputs)?;
+            }
+        } else if has_extension(&path, extensions) {
+            inputs.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn has_extension(path: &Path, extensions: &[String]) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|ext| extensions.iter().any(|e| e.eq_ignore_ascii_case(ext)))
+}
+
+/// Whether an output path contains `{placeholder}`s.
+pub fn is_template(output: &Path) -> bool {
+    output.to_string_lossy().contains('{')
+}
+
+/// Expands an output template such as `{dir}/{stem}.thumbs.jpg` for `input`.
+/// `{dir}` is the input's directory, `{stem}` its file name without
+/// extension, `{name}` the full file name and `{ext}` the extension.
+pub fn output_path(template: &Path, input: &Path) -> Result<PathBuf> {
+    let template = template.to_string_lossy();
+    if !template.contains('{') {
+        return Ok(PathBuf::from(template.as_ref()));
+    }
+
+    let lossy = |s: Option<&std::ffi::OsStr>| s.map(|s| s.to_string_lossy().into_owned());
+    let dir = match input.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_string_lossy().into_owned(),
+        _ => ".".to_string(),
+    };
+
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template.as_ref();
+    while let Some(open) = rest.find('{') {
+        out.push_str(&rest[..open]);
+        let after = &rest[open + 1..];
+        let close = after
+            .find('}')
+            .ok_or_else(|| ThumbsdownError::UnknownOutputPlaceholder(after.to_string()))?;
+        let name = &after[..close];
+        let value = match name {
+            "dir" => dir.clone(),
+            "stem" => lossy(input.file_stem()).unwrap_or_default(),
+            "name" => lossy(input.file_name()).unwrap_or_default(),
+            "ext" => lossy(input.extension()).unwrap_or_default(),
+            _ => return Err(ThumbsdownError::UnknownOutputPlaceholder(name.to_string())),
+        };
+        out.push_str(&value);
+        rest = &after[close + 1..];
+    }
+    out.push_str(rest);
+
+    Ok(PathBuf::from(out))
+}
+
+/// Path of page `page` (1-based) of a multi-page output:
+/// `out/sheet.png` -> `out/sheet-002.png`.
+pub fn page_path(output: &Path, page: usize) -> PathBuf {
+    let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match output.extension() {
+        Some(ext) => format!("{stem}-{page:03}.{}", ext.to_string_lossy()),
+        None => format!("{stem}-{page:03}"),
+    };
+    output.with_file_name(name)
+}
+
+/// The output file and any pages of a multi-page output that exist, e.g.
+/// left over from an earlier run.
+pub fn existing_pages(output: &Path) -> Vec<PathBuf> {
+    let pages = (1..)
+        .map(|page| page_path(output, page))
+        .take_while(|path| path.exists());
+    Some(output.to_path_buf())
+        .filter(|path| path.exists())
+        .into_iter()
+        .chain(pages)
+        .collect()
+}
+
+/// The output file, or the first page of a multi-page output, if either
+/// exists.
+pub fn existing_output(output: &Path) -> Option<PathBuf> {
+    [output.to_path_buf(), page_path(output, 1)]
+        .into_iter()
+        .find(|path| path.exists())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn extensions() -> Vec<String> {
+        DEFAULT_EXTENSIONS.iter().map(|e| e.to_string()).collect()
+    }
+
+    fn touch(path: &Path) {
+        std::fs::create_dir_all(path.parent().expect("parent")).expect("mkdir");
+        std::fs::write(path, b"fake").expect("write");
+    }
+
+    #[test]
+    fn collect_inputs_keeps_files_as_given() {
+        let inputs =
+            collect_inputs(&[PathBuf::from("missing.txt")], false, &extensions()).expect("collect");
+        assert_eq!(inputs, vec![PathBuf::from("missing.txt")]);
+    }
+
+    #[test]
+    fn collect_inputs_filters_directory_by_extension() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        touch(&dir.path().join("b.MKV"));
+        touch(&dir.path().join("a.mp4"));
+        touch(&dir.path().join("notes.txt"));
+        touch(&dir.path().join("sub/c.mp4"));
+
+        let inputs =
+            collect_inputs(&[dir.path().to_path_buf()], false, &extensions()).expect("collect");
+        assert_eq!(
+            inputs,
+            vec![dir.path().join("a.mp4"), dir.path().join("b.MKV")]
+        );
+    }
+
+    #[test]
+    fn collect_inputs_recurses_when_asked() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        touch(&dir.path().join("a.mp4"));
+        touch(&dir.path().join("sub/deeper/c.webm"));
+
+        let inputs =
+            collect_inputs(&[dir.path().to_path_buf()], true, &extensions()).expect("collect");
+        assert_eq!(
+            inputs,
+            vec![
+                dir.path().join("a.mp4"),
+                dir.path().join("sub/deeper/c.webm")
+            ]
+        );
+    }
+
+    #[test]
+    fn output_path_expands_placeholders() {
+        let output = output_path(
+            Path::new("{dir}/{stem}.thumbs.jpg"),
+            Path::new("/media/films/movie.mkv"),
+        )
+        .expect("output");
+        assert_eq!(output, PathBuf::from("/media/films/movie.thumbs.jpg"));
+    }
+
+    #[test]
+    fn output_path_uses_dot_for_bare_file_names() {
+        let output =
+            output_path(Path::new("{dir}/{name}.png"), Path::new("clip.mp4")).expect("output");
+        assert_eq!(output, PathBuf::from("./clip.mp4.png"));
+    }
+
+    #[test]
+    fn output_path_without_placeholders_is_unchanged() {
+        let output = output_path(Path::new("thumbs.png"), Path::new("a/b.mp4")).expect("output");
+        assert_eq!(output, PathBuf::from("thumbs.png"));
+    }
+
+    #[test]
+    fn page_path_numbers_before_extension() {
+        assert_eq!(
+            page_path(Path::new("out/sheet.png"), 2),
+            PathBuf::from("out/sheet-002.png")
+        );
+        assert_eq!(
+            page_path(Path::new("sheet"), 12),
+            PathBuf::from("sheet-012")
+        );
+    }
+
+    #[test]
+    fn existing_output_finds_first_page() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let output = dir.path().join("sheet.png");
+        assert_eq!(existing_output(&output), None);
+        touch(&dir.path().join("sheet-001.png"));
+        assert_eq!(
+            existing_output(&output),
+            Some(dir.path().join("sheet-001.png"))
+        );
+    }
+
+    #[test]
+    fn existing_pages_lists_output_and_pages() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let output = dir.path().join("sheet.png");
+        assert!(existing_pages(&output).is_empty());
+        for name in [
+            "sheet.png",
+            "sheet-001.png",
+            "sheet-002.png",
+            "sheet-004.png",
+        ] {
+            touch(&dir.path().join(name));
+        }
+        assert_eq!(
+            existing_pages(&output),
+            vec![
+                output.clone(),
+                dir.path().join("sheet-001.png"),
+                dir.path().join("sheet-002.png"),
+            ]
+        );
+    }
+
+    #[test]
+    fn output_path_rejects_unknown_placeholder() {
+        let err = output_path(Path::new("{basename}.png"), Path::new("a.mp4")).unwrap_err();
+        assert!(err.to_string().contains("{basename}"));
+    }
+}
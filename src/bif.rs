@@ -0,0 +1,165 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::encode;
+use crate::error::{Result, ThumbsdownError};
+use crate::select;
+use crate::video::{self, VideoInfo};
+
+/// File signature of a BIF (Base Index Frames) archive.
+pub const MAGIC: [u8; 8] = [0x89, b'B', b'I', b'F', 0x0D, 0x0A, 0x1A, 0x0A];
+pub const DEFAULT_INTERVAL: f64 = 10.0;
+pub const DEFAULT_WIDTH: u32 = 320;
+pub const DEFAULT_QUALITY: u8 = 85;
+
+const VERSION: u32 = 0;
+const HEADER_SIZE: usize = 64;
+const INDEX_END: u32 = 0xFFFF_FFFF;
+
+/// `interval` in whole milliseconds, as stored in the BIF header. Fails
+/// unless the interval is finite and at least half a millisecond.
+pub fn interval_ms(interval: f64) -> Result<u32> {
+    let ms = (interval * 1000.0).round();
+    if !(1.0..=u32::MAX as f64).contains(&ms) {
+        return Err(ThumbsdownError::InvalidInterval(format!("{interval}s")));
+    }
+    Ok(ms as u32)
+}
+
+/// Frame times every `interval` seconds from the start of the video.
+pub fn timestamps(info: &VideoInfo, interval: f64) -> Result<Vec<f64>> {
+    interval_ms(interval)?;
+    Ok(select::every(0.0, info.duration, interval))
+}
+
+/// Captures a frame every `interval` seconds (see `timestamps`) in a single
+/// pass, scaled to `width` with the video's display aspect ratio, and
+/// encodes it as JPEG. `on_frame` is called after each frame.
+pub fn capture(
+    video: &Path,
+    info: &VideoInfo,
+    interval: f64,
+    width: u32,
+    quality: u8,
+    mut on_frame: impl FnMut(),
+) -> Result<Vec<Vec<u8>>> {
+    // Even dimensions keep ffmpeg's scaler away from chroma rounding issues
+    let (display_width, display_height) = info.display_size();
+    let height = (width as u64 * display_height as u64 / display_width.max(1) as u64) as u32;
+    let (width, height) = ((width & !1).max(2), (height & !1).max(2));
+
+    let count = timestamps(info, interval)?.len();
+    let mut frames = Vec::with_capacity(count);
+    video::capture_every(video, interval, count, width, height, |_, frame| {
+        frames.push(encode::encode_jpeg(&frame, quality, false)?);
+        on_frame();
+        Ok(())
+    })?;
+    Ok(frames)
+}
+
+/// Writes JPEG `frames` taken every `interval` seconds as a BIF archive.
+pub fn save(path: &Path, interval: f64, frames: &[Vec<u8>]) -> Result<()> {
+    let interval_ms = interval_ms(interval)?;
+    let mut writer = BufWriter::new(File::create(path)?);
+    write(&mut writer, interval_ms, frames)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Writes the BIF header, the frame index and the frame data. Frame `i` is
+/// shown from `i * interval_ms` milliseconds.
+pub fn write<W: Write>(mut writer: W, interval_ms: u32, frames: &[Vec<u8>]) -> Result<()> {
+    let too_large = || ThumbsdownError::Encode("BIF archive exceeds 4 GiB".to_string());
+    let count = u32::try_from(frames.len()).map_err(|_| too_large())?;
+
+    let mut header = [0u8; HEADER_SIZE];
+    header[..8].copy_from_slice(&MAGIC);
+    header[8..12].copy_from_slice(&VERSION.to_le_bytes());
+    header[12..16].copy_from_slice(&count.to_le_bytes());
+    header[16..20].copy_from_slice(&interval_ms.to_le_bytes());
+    writer.write_all(&header)?;
+
+    // One (timestamp, offset) pair per frame plus the end marker
+    let mut offset = (HEADER_SIZE + (frames.len() + 1) * 8) as u64;
+    for (i, frame) in frames.iter().enumerate() {
+        let position = u32::try_from(offset).map_err(|_| too_large())?;
+        writer.write_all(&(i as u32).to_le_bytes())?;
+        writer.write_all(&position.to_le_bytes())?;
+        offset += frame.len() as u64;
+    }
+    let end = u32::try_from(offset).map_err(|_| too_large())?;
+    writer.write_all(&INDEX_END.to_le_bytes())?;
+    writer.write_all(&end.to_le_bytes())?;
+
+    for frame in frames {
+        writer.write_all(frame)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn u32_at(data: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(data[offset..offset + 4].try_into().expect("4 bytes"))
+    }
+
+    #[test]
+    fn write_lays_out_header_index_and_frames() {
+        let frames = vec![vec![1u8, 2, 3], vec![4u8, 5]];
+        let mut data = Vec::new();
+        write(&mut data, 10_000, &frames).expect("write");
+
+        assert_eq!(&data[..8], &MAGIC);
+        assert_eq!(u32_at(&data, 8), 0);
+        assert_eq!(u32_at(&data, 12), 2);
+        assert_eq!(u32_at(&data, 16), 10_000);
+        assert!(data[20..64].iter().all(|&b| b == 0));
+
+        // Index: header + 3 entries of 8 bytes puts the first frame at 88
+        assert_eq!((u32_at(&data, 64), u32_at(&data, 68)), (0, 88));
+        assert_eq!((u32_at(&data, 72), u32_at(&data, 76)), (1, 91));
+        assert_eq!((u32_at(&data, 80), u32_at(&data, 84)), (INDEX_END, 93));
+        assert_eq!(&data[88..], &[1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn timestamps_start_at_zero() {
+        let info = VideoInfo {
+            duration: 25.0,
+            ..Default::default()
+        };
+        assert_eq!(
+            timestamps(&info, 10.0).expect("timestamps"),
+            vec![0.0, 10.0, 20.0]
+        );
+    }
+
+    #[test]
+    fn interval_ms_rounds_to_milliseconds() {
+        assert_eq!(interval_ms(10.0).expect("interval"), 10_000);
+        assert_eq!(interval_ms(0.0015).expect("interval"), 2);
+    }
+
+    #[test]
+    fn rejects_invalid_interval() {
+        for interval in [0.0, -1.0, 0.0004, f64::NAN, f64::INFINITY] {
+            assert!(matches!(
+                interval_ms(interval),
+                Err(ThumbsdownError::InvalidInterval(_))
+            ));
+        }
+        let info = VideoInfo {
+            duration: 25.0,
+            ..Default::default()
+        };
+        assert!(timestamps(&info, 0.0).is_err());
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("video.bif");
+        assert!(save(&path, 0.0, &[]).is_err());
+        assert!(!path.exists());
+    }
+}
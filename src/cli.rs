@@ -1,33 +1,124 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use clap::Parser;
-
-use crate::error::{Result, ThumbsdownError};
+use serde::{Deserialize, Serialize};
+
+use thumbsdown::animation::{self, AnimationFormat, AnimationOptions};
+use thumbsdown::encode::{self, EncodeOptions, OutputFormat};
+use thumbsdown::grid::{Corner, TimestampOverlay};
+use thumbsdown::layout::{Aspect, Columns, SheetSize};
+use thumbsdown::quality::{self, FrameFilter};
+use thumbsdown::select::Selection;
+use thumbsdown::style::{Color, Style, Theme};
+use thumbsdown::timecode::{self, TimeSpec};
+use thumbsdown::video::ExtractMode;
+use thumbsdown::{batch, bif};
+use thumbsdown::{header, ContactSheet, Result, ThumbsdownError, VideoInfo};
+
+// Every option except the inputs, --preset and --print-config can also be set
+// in a config file (see `config`), keyed by its long name.
 
 /// Generate thumbnail grids from video files
-#[derive(Parser, Debug)]
+#[derive(Parser, Debug, Serialize, Deserialize)]
 #[command(name = "thumbsdown", version, about)]
+#[serde(rename_all = "kebab-case")]
 pub struct Args {
-    /// Path to the video file
-    pub video: PathBuf,
-
-    /// Start time in seconds
-    #[arg(short = 's', long, default_value_t = 1)]
-    pub start: u64,
+    /// Video files, or directories to search for videos
+    #[arg(required_unless_present = "print_config")]
+    #[serde(skip)]
+    pub videos: Vec<PathBuf>,
+
+    /// Search directory inputs recursively
+    #[arg(short = 'r', long)]
+    pub recursive: bool,
+
+    /// Video file extensions picked up from directory inputs
+    #[arg(long, value_delimiter = ',', default_values = batch::DEFAULT_EXTENSIONS.iter().copied())]
+    pub ext: Vec<String>,
+
+    /// Start time: seconds, [HH:]MM:SS[.mmm], a percentage such as 5% or a
+    /// negative offset from the end such as -2:00
+    #[arg(short = 's', long, default_value = "1", allow_hyphen_values = true)]
+    pub start: TimeSpec,
+
+    /// End time, in the same forms as --start (default: end of the video)
+    #[arg(short = 'e', long, allow_hyphen_values = true)]
+    pub end: Option<TimeSpec>,
+
+    /// Comma-separated capture times instead of evenly spaced ones
+    #[arg(
+        long,
+        value_delimiter = ',',
+        allow_hyphen_values = true,
+        conflicts_with_all = ["start", "end", "thumbs", "select"]
+    )]
+    pub at: Vec<TimeSpec>,
 
     /// Number of thumbnails to generate
     #[arg(short = 't', long, default_value_t = 20)]
     pub thumbs: u32,
 
-    /// Number of columns in the grid
-    #[arg(short = 'c', long, default_value_t = 5)]
-    pub columns: u32,
-
-    /// Output file path
+    /// Capture one thumbnail every interval, e.g. 30s, 2m or 1:30, instead
+    /// of a fixed number
+    #[arg(long, value_parser = timecode::parse_interval, conflicts_with_all = ["thumbs", "select", "at"])]
+    #[serde(deserialize_with = "timecode::deserialize_optional_interval")]
+    pub every: Option<f64>,
+
+    /// Maximum thumbnails per sheet; more are split over numbered pages
+    /// (thumbs-001.png, thumbs-002.png, ...)
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub max_thumbs: Option<u32>,
+
+    /// Maximum grid rows per page; more are split over numbered pages
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub rows_per_page: Option<u32>,
+
+    /// Maximum page height in pixels, header included
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub max_height: Option<u32>,
+
+    /// Number of columns in the grid, or "auto" to pick the count that best
+    /// fills --sheet-size or comes closest to --aspect
+    #[arg(short = 'c', long, default_value_t = Columns::Count(5))]
+    pub columns: Columns,
+
+    /// Number of rows to spread the thumbnails over, instead of --columns
+    #[arg(long, conflicts_with = "columns", value_parser = clap::value_parser!(u32).range(1..))]
+    pub rows: Option<u32>,
+
+    /// Sheet shape --columns auto aims for, as W:H or a ratio such as 2.39
+    #[arg(long, default_value_t = Aspect::default())]
+    pub aspect: Aspect,
+
+    /// Output file path; with several inputs a template using {dir}, {stem},
+    /// {name} or {ext}, e.g. "{dir}/{stem}.thumbs.jpg". A .pdf output holds
+    /// all pages
     #[arg(short = 'o', long, default_value = "thumbs.png")]
     pub output: PathBuf,
 
-    /// Temporary directory (default: system temp)
+    /// Output image format (default: from the output extension)
+    #[arg(short = 'F', long, value_enum)]
+    pub format: Option<OutputFormat>,
+
+    /// Quality 1-100 for JPEG, lossy WebP and AVIF output
+    #[arg(short = 'q', long, value_parser = clap::value_parser!(u8).range(1..=100))]
+    pub quality: Option<u8>,
+
+    /// Write progressive JPEG
+    #[arg(long)]
+    pub progressive: bool,
+
+    /// Write lossless WebP
+    #[arg(long)]
+    pub lossless: bool,
+
+    /// Color transparent areas are flattened onto in formats without alpha
+    /// (JPEG, PDF) (default: white)
+    #[arg(long)]
+    pub flatten: Option<Color>,
+
+    /// Deprecated and ignored: frames are streamed from ffmpeg and kept
+    /// frames are written straight to --keep-frames
     #[arg(short = 'T', long)]
     pub temp: Option<PathBuf>,
 
@@ -35,6 +126,110 @@ pub struct Args {
     #[arg(short = 'w', long, default_value_t = 320)]
     pub width: u32,
 
+    /// Maximum sheet size as WIDTHxHEIGHT, or a width alone; the thumbnail
+    /// width is chosen to fill it
+    #[arg(long, conflicts_with = "width")]
+    pub sheet_size: Option<SheetSize>,
+
+    /// Label every thumbnail with its capture time
+    #[arg(long)]
+    pub timestamps: bool,
+
+    /// Corner of the thumbnail the timestamp label is drawn in
+    #[arg(long, value_enum, default_value_t = Corner::BottomRight)]
+    pub timestamp_position: Corner,
+
+    /// Font size of the timestamp label in pixels
+    #[arg(long, default_value_t = 14.0)]
+    pub timestamp_size: f32,
+
+    /// Append the frame number to the timestamp label
+    #[arg(long)]
+    pub frame_numbers: bool,
+
+    /// Header text with placeholders such as {filename}, {duration}, {codec},
+    /// {resolution}, {size} or {bitrate}; lines split on newlines or "\n"
+    #[arg(long, conflicts_with = "no_header")]
+    pub header_template: Option<String>,
+
+    /// Omit the metadata header above the grid
+    #[arg(long)]
+    pub no_header: bool,
+
+    /// Color scheme; the options below override single parts of it
+    #[arg(long, value_enum, default_value_t = Theme::Light)]
+    pub theme: Theme,
+
+    /// Sheet background, as #rrggbb, #rrggbbaa, #rgb or white, black, gray
+    /// or transparent
+    #[arg(long)]
+    pub background: Option<Color>,
+
+    /// Color of the border around each thumbnail
+    #[arg(long)]
+    pub border_color: Option<Color>,
+
+    /// Width in pixels of the border around each thumbnail (default: 2)
+    #[arg(long)]
+    pub border_width: Option<u32>,
+
+    /// Space in pixels between thumbnails (default: 0)
+    #[arg(long)]
+    pub gutter: Option<u32>,
+
+    /// Space in pixels around the header and grid (default: 0)
+    #[arg(long)]
+    pub margin: Option<u32>,
+
+    /// Color of the header text
+    #[arg(long)]
+    pub header_color: Option<Color>,
+
+    /// Background of the header band
+    #[arg(long)]
+    pub header_background: Option<Color>,
+
+    /// Write a tightly packed sprite without borders, header or labels, plus
+    /// a WebVTT file (output with a .vtt extension) for player seek previews
+    #[arg(long, conflicts_with = "no_grid")]
+    pub vtt: bool,
+
+    /// How thumbnail timestamps are chosen
+    #[arg(long, value_enum, default_value_t = Selection::Uniform)]
+    pub select: Selection,
+
+    /// Minimum scene-change score (0.0-1.0) counted as a cut with --select scenes
+    #[arg(long, default_value_t = 0.3)]
+    pub scene_threshold: f64,
+
+    /// Frame extraction strategy
+    #[arg(long, value_enum, default_value_t = ExtractMode::PerFrame)]
+    pub extract: ExtractMode,
+
+    /// Reject dark, blank and blurry frames and re-capture them nearby
+    #[arg(long)]
+    pub frame_filter: bool,
+
+    /// Minimum mean brightness (0-255) of a frame accepted by --frame-filter
+    #[arg(long, default_value_t = quality::DEFAULT_MIN_BRIGHTNESS)]
+    pub min_brightness: f64,
+
+    /// Minimum contrast (luma standard deviation) of an accepted frame
+    #[arg(long, default_value_t = quality::DEFAULT_MIN_CONTRAST)]
+    pub min_contrast: f64,
+
+    /// Minimum sharpness (Laplacian variance) of an accepted frame
+    #[arg(long, default_value_t = quality::DEFAULT_MIN_SHARPNESS)]
+    pub min_sharpness: f64,
+
+    /// Re-capture attempts for a rejected frame
+    #[arg(long, default_value_t = quality::DEFAULT_RETRIES)]
+    pub retries: u32,
+
+    /// Number of frames to capture in parallel (default: available cores)
+    #[arg(short = 'j', long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub jobs: Option<u32>,
+
     /// Enable verbose output
     #[arg(short = 'v', long)]
     pub verbose: bool,
@@ -43,22 +238,234 @@ pub struct Args {
     #[arg(short = 'f', long)]
     pub force: bool,
 
+    /// Skip inputs whose outputs are up to date and regenerate stale ones;
+    /// a fingerprint of the input and settings is kept next to the sheet, or
+    /// the BIF or animation with --no-grid
+    #[arg(short = 'u', long, conflicts_with = "force")]
+    pub update: bool,
+
     /// Keep extracted frames in specified directory
     #[arg(short = 'k', long)]
     pub keep_frames: Option<PathBuf>,
 
-    /// Skip grid generation (only extract frames, requires --keep-frames)
+    /// Skip grid generation (only extract frames, requires --keep-frames,
+    /// --bif or --animate)
     #[arg(long)]
     pub no_grid: bool,
+
+    /// Also write a JSON manifest with the video metadata, the settings and
+    /// each tile's time and rectangle; with several inputs a template like
+    /// --output, e.g. "{dir}/{stem}.thumbs.json"
+    #[arg(long, conflicts_with = "no_grid")]
+    pub manifest: Option<PathBuf>,
+
+    /// Also write a Roku BIF trickplay archive; with several inputs a template
+    /// like --output, e.g. "{dir}/{stem}.bif"
+    #[arg(long)]
+    pub bif: Option<PathBuf>,
+
+    /// Interval between BIF frames, e.g. 10s or 5
+    #[arg(long, value_parser = timecode::parse_interval, default_value = "10s")]
+    #[serde(deserialize_with = "timecode::deserialize_interval")]
+    pub bif_interval: f64,
+
+    /// Width of BIF frames in pixels
+    #[arg(long, default_value_t = bif::DEFAULT_WIDTH, value_parser = clap::value_parser!(u32).range(2..))]
+    pub bif_width: u32,
+
+    /// JPEG quality 1-100 for BIF frames
+    #[arg(long, default_value_t = bif::DEFAULT_QUALITY, value_parser = clap::value_parser!(u8).range(1..=100))]
+    pub bif_quality: u8,
+
+    /// Also write an animated preview cycling through the thumbnails (.gif,
+    /// .webp, or .png/.apng); with several inputs a template like --output
+    #[arg(long)]
+    pub animate: Option<PathBuf>,
+
+    /// How long each thumbnail stays on screen in the animation, in
+    /// milliseconds
+    #[arg(long, default_value_t = animation::DEFAULT_DELAY_MS, value_parser = clap::value_parser!(u32).range(1..))]
+    pub frame_delay: u32,
+
+    /// Number of times the animation plays (0 = forever)
+    #[arg(long, default_value_t = 0)]
+    pub loops: u32,
+
+    /// Consecutive video frames shown at each thumbnail of the animation,
+    /// played at the video's frame rate
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    pub burst: u32,
+
+    /// Apply a named preset from the config files
+    #[arg(short = 'p', long)]
+    #[serde(skip)]
+    pub preset: Option<String>,
+
+    /// Print the settings merged from config files and flags, then exit
+    #[arg(long)]
+    #[serde(skip)]
+    pub print_config: bool,
 }
 
-pub fn validate(args: &Args) -> Result<()> {
-    if !args.video.exists() {
-        return Err(ThumbsdownError::InputNotFound(args.video.clone()));
+/// Everything `--update` compares to decide whether an input's outputs are
+/// stale: the sheet's parameters plus the settings of the optional outputs
+/// written with it. Outputs that are not requested are left out, so enabling
+/// one regenerates the input.
+#[derive(Debug, Serialize)]
+pub struct FingerprintParams<'a> {
+    #[serde(flatten)]
+    pub sheet: &'a ContactSheet,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub manifest: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bif: Option<BifParams>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub animate: Option<AnimationOptions>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BifParams {
+    pub interval: f64,
+    pub width: u32,
+    pub quality: u8,
+}
+
+impl Args {
+    pub fn fingerprint_params<'a>(&self, sheet: &'a ContactSheet) -> FingerprintParams<'a> {
+        FingerprintParams {
+            sheet,
+            manifest: self.manifest.is_some(),
+            bif: self.bif.as_ref().map(|_| BifParams {
+                interval: self.bif_interval,
+                width: self.bif_width,
+                quality: self.bif_quality,
+            }),
+            animate: self.animate.as_ref().map(|_| self.animation_options()),
+        }
     }
 
-    if args.output.exists() && !args.force {
-        return Err(ThumbsdownError::OutputExists(args.output.clone()));
+    /// The manifest, BIF and animation paths for `video`, if requested.
+    pub fn extra_outputs(&self, video: &Path) -> Result<Vec<PathBuf>> {
+        [&self.manifest, &self.bif, &self.animate]
+            .into_iter()
+            .flatten()
+            .map(|template| batch::output_path(template, video))
+            .collect()
+    }
+
+    /// Every file written for `video`: the sheet unless --no-grid, then the
+    /// extra outputs. `--update` keeps its fingerprint next to the first.
+    pub fn outputs(&self, video: &Path, output: &Path) -> Result<Vec<PathBuf>> {
+        let sheet = (!self.no_grid).then(|| output.to_path_buf());
+        Ok(sheet
+            .into_iter()
+            .chain(self.extra_outputs(video)?)
+            .collect())
+    }
+
+    pub fn contact_sheet(&self, video: &Path, output: &Path) -> ContactSheet {
+        let mut sheet = ContactSheet::new(video)
+            .start(self.start)
+            .thumbs(self.thumbs)
+            .max_thumbs(self.max_thumbs)
+            .rows_per_page(self.rows_per_page)
+            .max_height(self.max_height)
+            .columns(self.rows.map_or(self.columns, Columns::Rows))
+            .width(self.width)
+            .sheet_size(self.sheet_size)
+            .aspect(self.aspect)
+            .encode(EncodeOptions {
+                format: self.format,
+                quality: self.quality,
+                progressive: self.progressive,
+                lossless: self.lossless,
+                flatten: self.flatten,
+            })
+            .timestamp_overlay(self.timestamp_overlay())
+            .header(!self.no_header)
+            .style(self.style())
+            .vtt(self.vtt)
+            .select(self.select)
+            .scene_threshold(self.scene_threshold)
+            .extract(self.extract)
+            .frame_filter(self.frame_filter())
+            .jobs(self.jobs.unwrap_or(0) as usize)
+            .verbose(self.verbose);
+        if !self.no_grid {
+            sheet = sheet.output(output);
+        }
+        if let Some(interval) = self.every {
+            sheet = sheet.every(interval);
+        }
+        if let Some(end) = self.end {
+            sheet = sheet.end(end);
+        }
+        if !self.at.is_empty() {
+            sheet = sheet.at(self.at.iter().copied());
+        }
+        if let Some(ref template) = self.header_template {
+            sheet = sheet.header_template(template);
+        }
+        if let Some(ref dir) = self.keep_frames {
+            sheet = sheet.keep_frames(dir);
+        }
+        sheet
+    }
+
+    pub fn animation_options(&self) -> AnimationOptions {
+        AnimationOptions {
+            delay_ms: self.frame_delay,
+            loops: self.loops,
+            burst: self.burst,
+            quality: self.quality,
+            lossless: self.lossless,
+        }
+    }
+
+    fn style(&self) -> Style {
+        let theme = self.theme.style();
+        Style {
+            background: self.background.unwrap_or(theme.background),
+            border_color: self.border_color.unwrap_or(theme.border_color),
+            border_width: self.border_width.unwrap_or(theme.border_width),
+            gutter: self.gutter.unwrap_or(theme.gutter),
+            margin: self.margin.unwrap_or(theme.margin),
+            header_text: self.header_color.unwrap_or(theme.header_text),
+            header_background: self.header_background.unwrap_or(theme.header_background),
+        }
+    }
+
+    fn timestamp_overlay(&self) -> Option<TimestampOverlay> {
+        if !self.timestamps {
+            return None;
+        }
+        Some(TimestampOverlay {
+            corner: self.timestamp_position,
+            font_size: self.timestamp_size,
+            frame_numbers: self.frame_numbers,
+        })
+    }
+
+    fn frame_filter(&self) -> Option<FrameFilter> {
+        if !self.frame_filter {
+            return None;
+        }
+        Some(FrameFilter {
+            min_brightness: self.min_brightness,
+            min_contrast: self.min_contrast,
+            min_sharpness: self.min_sharpness,
+            retries: self.retries,
+        })
+    }
+}
+
+/// Checks options that apply to the whole run.
+pub fn validate(args: &Args) -> Result<()> {
+    if !args.no_grid {
+        let format = encode::resolve_format(&args.output, args.format)?;
+        if args.vtt && format == OutputFormat::Pdf {
+            return Err(ThumbsdownError::VttNeedsImage);
+        }
     }
 
     if let Some(ref temp) = args.temp {
@@ -73,104 +480,241 @@ pub fn validate(args: &Args) -> Result<()> {
         }
     }
 
-    if args.no_grid && args.keep_frames.is_none() {
+    if args.no_grid && args.keep_frames.is_none() && args.bif.is_none() && args.animate.is_none() {
         return Err(ThumbsdownError::NoGridWithoutKeepFrames);
     }
 
+    if args.update && args.no_grid && args.bif.is_none() && args.animate.is_none() {
+        return Err(ThumbsdownError::UpdateWithoutOutputs);
+    }
+
+    if let Some(ref template) = args.header_template {
+        header::expand_template(template, &VideoInfo::default())?;
+    }
+
+    if args.bif.is_some() {
+        bif::interval_ms(args.bif_interval)?;
+    }
+
+    batch::output_path(&args.output, Path::new("video.mp4"))?;
+    for template in [&args.bif, &args.manifest].into_iter().flatten() {
+        batch::output_path(template, Path::new("video.mp4"))?;
+    }
+    if let Some(ref animate) = args.animate {
+        AnimationFormat::resolve(&batch::output_path(animate, Path::new("video.mp4"))?)?;
+    }
+
+    Ok(())
+}
+
+/// Checks a single input and the outputs it would be written to.
+pub fn validate_job(args: &Args, video: &Path, output: &Path) -> Result<()> {
+    if !video.exists() {
+        return Err(ThumbsdownError::InputNotFound(video.to_path_buf()));
+    }
+
+    for path in args.extra_outputs(video)? {
+        if path.exists() && !args.force && !args.update {
+            return Err(ThumbsdownError::OutputExists(path));
+        }
+    }
+
+    if !args.no_grid && !args.force && !args.update {
+        if let Some(existing) = batch::existing_output(output) {
+            return Err(ThumbsdownError::OutputExists(existing));
+        }
+    }
+
     Ok(())
 }
 
 #[cfg(test)]
 mod tests {
+    use std::ffi::OsString;
+
     use super::*;
 
+    fn args(video: impl Into<PathBuf>) -> Args {
+        Args::parse_from([OsString::from("thumbsdown"), video.into().into_os_string()])
+    }
+
     #[test]
-    fn validate_rejects_missing_video() {
-        let args = Args {
-            video: PathBuf::from("nonexistent_video.mp4"),
-            start: 1,
-            thumbs: 20,
-            columns: 5,
-            output: PathBuf::from("out.png"),
-            temp: None,
-            width: 320,
-            verbose: false,
-            force: false,
-            keep_frames: None,
-            no_grid: false,
-        };
-        let err = validate(&args).unwrap_err();
+    fn validate_job_rejects_missing_video() {
+        let args = args("nonexistent_video.mp4");
+        let err = validate_job(
+            &args,
+            Path::new("nonexistent_video.mp4"),
+            Path::new("out.png"),
+        )
+        .unwrap_err();
         assert!(err.to_string().contains("does not exist"));
     }
 
     #[test]
-    fn validate_rejects_existing_output_without_force() {
+    fn validate_job_rejects_existing_output_without_force() {
         let dir = tempfile::tempdir().expect("tempdir");
         let video = dir.path().join("video.mp4");
         std::fs::write(&video, b"fake").expect("write");
         let output = dir.path().join("existing.png");
         std::fs::write(&output, b"fake").expect("write");
 
-        let args = Args {
-            video,
-            start: 1,
-            thumbs: 20,
-            columns: 5,
-            output,
-            temp: None,
-            width: 320,
-            verbose: false,
-            force: false,
-            keep_frames: None,
-            no_grid: false,
-        };
-        let err = validate(&args).unwrap_err();
+        let args = args(&video);
+        let err = validate_job(&args, &video, &output).unwrap_err();
         assert!(err.to_string().contains("already exists"));
     }
 
     #[test]
-    fn validate_allows_force_overwrite() {
+    fn validate_job_allows_force_overwrite() {
         let dir = tempfile::tempdir().expect("tempdir");
         let video = dir.path().join("video.mp4");
         std::fs::write(&video, b"fake").expect("write");
         let output = dir.path().join("existing.png");
         std::fs::write(&output, b"fake").expect("write");
 
-        let args = Args {
-            video,
-            start: 1,
-            thumbs: 20,
-            columns: 5,
-            output,
-            temp: None,
-            width: 320,
-            verbose: false,
-            force: true,
-            keep_frames: None,
-            no_grid: false,
-        };
+        let mut args = args(&video);
+        args.force = true;
+        assert!(validate_job(&args, &video, &output).is_ok());
+    }
+
+    #[test]
+    fn validate_job_allows_existing_output_with_update() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let video = dir.path().join("video.mp4");
+        std::fs::write(&video, b"fake").expect("write");
+        let output = dir.path().join("existing.png");
+        std::fs::write(&output, b"fake").expect("write");
+
+        let mut args = args(&video);
+        args.update = true;
+        assert!(validate_job(&args, &video, &output).is_ok());
+    }
+
+    #[test]
+    fn update_conflicts_with_force() {
+        let result = Args::try_parse_from(["thumbsdown", "--update", "--force", "a.mp4"]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn validate_update_with_no_grid_needs_tracked_output() {
+        let mut args = args("video.mp4");
+        args.update = true;
+        args.no_grid = true;
+        args.keep_frames = Some(std::env::temp_dir());
+        assert!(matches!(
+            validate(&args),
+            Err(ThumbsdownError::UpdateWithoutOutputs)
+        ));
+
+        args.bif = Some(PathBuf::from("{dir}/{stem}.bif"));
+        assert!(validate(&args).is_ok());
+        assert_eq!(
+            args.outputs(Path::new("dir/video.mp4"), Path::new("dir/video.png"))
+                .expect("outputs"),
+            vec![PathBuf::from("dir/video.bif")]
+        );
+    }
+
+    #[test]
+    fn validate_rejects_unknown_output_placeholder() {
+        let mut args = args("video.mp4");
+        args.output = PathBuf::from("{dir}/{basename}.png");
+        let err = validate(&args).unwrap_err();
+        assert!(err.to_string().contains("{basename}"));
+    }
+
+    #[test]
+    fn validate_rejects_unknown_animation_format() {
+        let mut args = args("video.mp4");
+        args.animate = Some(PathBuf::from("{stem}.preview.mp4"));
+        assert!(matches!(
+            validate(&args),
+            Err(ThumbsdownError::UnsupportedAnimationFormat(_))
+        ));
+
+        args.animate = Some(PathBuf::from("{stem}.preview.webp"));
+        args.no_grid = true;
         assert!(validate(&args).is_ok());
     }
 
+    #[test]
+    fn style_overrides_theme() {
+        let args = Args::parse_from([
+            "thumbsdown",
+            "--theme",
+            "dark",
+            "--border-color",
+            "#ff0000",
+            "--gutter",
+            "6",
+            "a.mp4",
+        ]);
+        let style = args.style();
+        assert_eq!(style.background, Theme::Dark.style().background);
+        assert_eq!(style.border_color, Color::rgb(255, 0, 0));
+        assert_eq!(style.gutter, 6);
+        assert!(Args::try_parse_from(["thumbsdown", "--background", "nope", "a.mp4"]).is_err());
+    }
+
+    #[test]
+    fn parses_multiple_inputs_and_extensions() {
+        let args = Args::parse_from(["thumbsdown", "-r", "--ext", "mp4,mkv", "a.mp4", "dir"]);
+        assert_eq!(
+            args.videos,
+            vec![PathBuf::from("a.mp4"), PathBuf::from("dir")]
+        );
+        assert_eq!(args.ext, vec!["mp4", "mkv"]);
+        assert!(args.recursive);
+    }
+
+    #[test]
+    fn parses_time_specs() {
+        let args = Args::parse_from(["thumbsdown", "-s", "5%", "--end", "-2:00", "a.mp4"]);
+        assert_eq!(args.start, TimeSpec::Percent(5.0));
+        assert_eq!(args.end, Some(TimeSpec::FromEnd(120.0)));
+
+        let args = Args::parse_from(["thumbsdown", "--at", "0:30,-10,50%", "a.mp4"]);
+        assert_eq!(
+            args.at,
+            vec![
+                TimeSpec::Seconds(30.0),
+                TimeSpec::FromEnd(10.0),
+                TimeSpec::Percent(50.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_interval_and_page_cap() {
+        let args = Args::parse_from([
+            "thumbsdown",
+            "--every",
+            "1m30s",
+            "--max-thumbs",
+            "40",
+            "a.mp4",
+        ]);
+        assert_eq!(args.every, Some(90.0));
+        assert_eq!(args.max_thumbs, Some(40));
+        assert!(
+            Args::try_parse_from(["thumbsdown", "--every", "30s", "-t", "5", "a.mp4"]).is_err()
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_start() {
+        assert!(Args::try_parse_from(["thumbsdown", "-s", "1:75", "a.mp4"]).is_err());
+    }
+
     #[test]
     fn validate_rejects_nonexistent_temp_dir() {
         let dir = tempfile::tempdir().expect("tempdir");
         let video = dir.path().join("video.mp4");
         std::fs::write(&video, b"fake").expect("write");
 
-        let args = Args {
-            video,
-            start: 1,
-            thumbs: 20,
-            columns: 5,
-            output: PathBuf::from("out.png"),
-            temp: Some(PathBuf::from("/nonexistent_dir_xyz")),
-            width: 320,
-            verbose: false,
-            force: false,
-            keep_frames: None,
-            no_grid: false,
-        };
+        let mut args = args(video);
+        args.output = PathBuf::from("out.png");
+        args.temp = Some(PathBuf::from("/nonexistent_dir_xyz"));
         let err = validate(&args).unwrap_err();
         assert!(err.to_string().contains("does not exist"));
     }
@@ -181,19 +725,9 @@ mod tests {
         let video = dir.path().join("video.mp4");
         std::fs::write(&video, b"fake").expect("write");
 
-        let args = Args {
-            video,
-            start: 1,
-            thumbs: 20,
-            columns: 5,
-            output: PathBuf::from("out.png"),
-            temp: None,
-            width: 320,
-            verbose: false,
-            force: false,
-            keep_frames: Some(PathBuf::from("/nonexistent_dir_xyz")),
-            no_grid: false,
-        };
+        let mut args = args(video);
+        args.output = PathBuf::from("out.png");
+        args.keep_frames = Some(PathBuf::from("/nonexistent_dir_xyz"));
         let err = validate(&args).unwrap_err();
         assert!(err.to_string().contains("keep-frames"));
     }
@@ -204,43 +738,145 @@ mod tests {
         let video = dir.path().join("video.mp4");
         std::fs::write(&video, b"fake").expect("write");
 
-        let args = Args {
-            video,
-            start: 1,
-            thumbs: 20,
-            columns: 5,
-            output: PathBuf::from("out.png"),
-            temp: None,
-            width: 320,
-            verbose: false,
-            force: false,
-            keep_frames: None,
-            no_grid: true,
-        };
+        let mut args = args(video);
+        args.output = PathBuf::from("out.png");
+        args.no_grid = true;
         let err = validate(&args).unwrap_err();
         assert!(err.to_string().contains("--no-grid"));
         assert!(err.to_string().contains("--keep-frames"));
     }
 
     #[test]
-    fn validate_allows_no_grid_with_keep_frames() {
+    fn validate_rejects_unknown_header_placeholder() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let video = dir.path().join("video.mp4");
+        std::fs::write(&video, b"fake").expect("write");
+
+        let mut args = args(video);
+        args.output = PathBuf::from("out.png");
+        args.header_template = Some("{filename} {bogus}".to_string());
+        let err = validate(&args).unwrap_err();
+        assert!(err.to_string().contains("{bogus}"));
+    }
+
+    #[test]
+    fn validate_rejects_format_extension_mismatch() {
         let dir = tempfile::tempdir().expect("tempdir");
         let video = dir.path().join("video.mp4");
         std::fs::write(&video, b"fake").expect("write");
 
-        let args = Args {
-            video,
-            start: 1,
-            thumbs: 20,
-            columns: 5,
-            output: PathBuf::from("out.png"),
-            temp: None,
-            width: 320,
-            verbose: false,
-            force: false,
-            keep_frames: Some(dir.path().to_path_buf()),
-            no_grid: true,
+        let mut args = args(video);
+        args.output = PathBuf::from("out.png");
+        args.format = Some(OutputFormat::Jpeg);
+        let err = validate(&args).unwrap_err();
+        assert!(err.to_string().contains("--format"));
+    }
+
+    #[test]
+    fn validate_rejects_vtt_with_pdf() {
+        let mut args = args("video.mp4");
+        args.output = PathBuf::from("sprite.pdf");
+        args.vtt = true;
+        assert!(matches!(
+            validate(&args).unwrap_err(),
+            ThumbsdownError::VttNeedsImage
+        ));
+    }
+
+    #[test]
+    fn fingerprint_params_cover_extra_outputs() {
+        let params = |argv: &[&str]| {
+            let args = Args::parse_from(argv);
+            let sheet = args.contact_sheet(Path::new("a.mp4"), Path::new("out.png"));
+            serde_json::to_value(args.fingerprint_params(&sheet)).expect("json")
         };
+        let plain = params(&["thumbsdown", "a.mp4"]);
+        assert_eq!(plain["thumbs"], 20);
+        assert!(plain.get("manifest").is_none() && plain.get("bif").is_none());
+
+        let with_bif = params(&["thumbsdown", "--bif", "a.bif", "a.mp4"]);
+        assert_eq!(with_bif["bif"]["interval"], 10.0);
+        assert_eq!(with_bif["bif"]["quality"], 85);
+        let sheet_quality = params(&["thumbsdown", "-q", "50", "--bif", "a.bif", "a.mp4"]);
+        assert_eq!(sheet_quality["bif"], with_bif["bif"]);
+        let longer = params(&[
+            "thumbsdown",
+            "--bif",
+            "a.bif",
+            "--bif-interval",
+            "5",
+            "a.mp4",
+        ]);
+        assert_ne!(with_bif, longer);
+
+        let with_extras = params(&[
+            "thumbsdown",
+            "--manifest",
+            "a.json",
+            "--animate",
+            "a.gif",
+            "a.mp4",
+        ]);
+        assert_eq!(with_extras["manifest"], true);
+        assert_eq!(with_extras["animate"]["loops"], 0);
+    }
+
+    #[test]
+    fn rows_replace_columns() {
+        let args = Args::parse_from(["thumbsdown", "--rows", "3", "--aspect", "4:3", "a.mp4"]);
+        let sheet = args.contact_sheet(Path::new("a.mp4"), Path::new("out.png"));
+        let params = serde_json::to_value(&sheet).expect("json");
+        assert_eq!(params["columns"], "3 rows");
+        assert_eq!(params["aspect"], "4:3");
+        assert!(Args::try_parse_from(["thumbsdown", "-c", "4", "--rows", "2", "a.mp4"]).is_err());
+        assert!(
+            Args::try_parse_from(["thumbsdown", "--sheet-size", "800", "-w", "200", "a.mp4"])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn validate_allows_no_grid_with_bif() {
+        let mut args = args("video.mp4");
+        args.no_grid = true;
+        args.bif = Some(PathBuf::from("{dir}/{stem}.bif"));
+        assert!(validate(&args).is_ok());
+    }
+
+    #[test]
+    fn validate_rejects_sub_millisecond_bif_interval() {
+        let mut args = args("video.mp4");
+        args.bif = Some(PathBuf::from("{dir}/{stem}.bif"));
+        args.bif_interval = 0.0001;
+        assert!(matches!(
+            validate(&args),
+            Err(ThumbsdownError::InvalidInterval(_))
+        ));
+    }
+
+    #[test]
+    fn validate_job_rejects_existing_bif() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let video = dir.path().join("video.mp4");
+        std::fs::write(&video, b"fake").expect("write");
+        std::fs::write(dir.path().join("video.bif"), b"fake").expect("write");
+
+        let mut args = args(&video);
+        args.bif = Some(PathBuf::from("{dir}/{stem}.bif"));
+        let err = validate_job(&args, &video, &dir.path().join("out.png")).unwrap_err();
+        assert!(err.to_string().contains("video.bif"));
+    }
+
+    #[test]
+    fn validate_allows_no_grid_with_keep_frames() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let video = dir.path().join("video.mp4");
+        std::fs::write(&video, b"fake").expect("write");
+
+        let mut args = args(video);
+        args.output = PathBuf::from("out.png");
+        args.keep_frames = Some(dir.path().to_path_buf());
+        args.no_grid = true;
         assert!(validate(&args).is_ok());
     }
 }
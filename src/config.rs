@@ -0,0 +1,406 @@
+use std::path::{Path, PathBuf};
+
+use clap::parser::ValueSource;
+use clap::{ArgMatches, Command, CommandFactory, FromArgMatches};
+use serde_json::{Map, Value};
+
+use thumbsdown::{Result, ThumbsdownError};
+
+use crate::cli::Args;
+
+/// Name of the project-local config file, looked up in the current directory.
+pub const LOCAL_FILE: &str = "thumbsdown.toml";
+
+/// A parsed config file. Top-level keys are settings; `[preset.<name>]`
+/// tables hold settings applied with `--preset <name>`.
+#[derive(Debug)]
+pub struct ConfigFile {
+    pub path: PathBuf,
+    settings: toml::Table,
+    presets: toml::Table,
+}
+
+/// Parses the command line and layers the config files under it.
+pub fn parse_args() -> Result<Args> {
+    let matches = Args::command().get_matches();
+    let args = Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    let files = load(&default_paths())?;
+    apply(args, &matches, &files)
+}
+
+/// Config file locations, lowest priority first: the user config under
+/// `$XDG_CONFIG_HOME` (or `~/.config`), then `thumbsdown.toml` in the
+/// current directory.
+pub fn default_paths() -> Vec<PathBuf> {
+    let config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")));
+
+    let mut paths = Vec::new();
+    if let Some(dir) = config_home {
+        paths.push(dir.join("thumbsdown").join("config.toml"));
+    }
+    paths.push(PathBuf::from(LOCAL_FILE));
+    paths
+}
+
+/// Reads the config files that exist among `paths`.
+pub fn load(paths: &[PathBuf]) -> Result<Vec<ConfigFile>> {
+    let mut files = Vec::new();
+    for path in paths {
+        if !path.is_file() {
+            continue;
+        }
+        let text = std::fs::read_to_string(path)?;
+        files.push(parse(path, &text)?);
+    }
+    Ok(files)
+}
+
+fn parse(path: &Path, text: &str) -> Result<ConfigFile> {
+    let invalid = |reason: String| ThumbsdownError::InvalidConfig {
+        path: path.to_path_buf(),
+        reason,
+    };
+
+    let mut settings: toml::Table = text
+        .parse()
+        .map_err(|e: toml::de::Error| invalid(e.message().to_string()))?;
+    let presets = match settings.remove("preset") {
+        None => toml::Table::new(),
+        Some(toml::Value::Table(presets)) => presets,
+        Some(_) => return Err(invalid("`preset` must be a table".to_string())),
+    };
+
+    Ok(ConfigFile {
+        path: path.to_path_buf(),
+        settings,
+        presets,
+    })
+}
+
+/// Applies the config files to `args` in order, then the selected preset.
+/// Options given on the command line always win, also over config values
+/// they conflict with. A later layer likewise drops earlier values that
+/// conflict with its own, while conflicting values within one layer are an
+/// error.
+pub fn apply(args: Args, matches: &ArgMatches, files: &[ConfigFile]) -> Result<Args> {
+    let mut layers: Vec<(&Path, &toml::Table)> = files
+        .iter()
+        .map(|file| (file.path.as_path(), &file.settings))
+        .collect();
+
+    if let Some(ref name) = args.preset {
+        let mut found = false;
+        for file in files {
+            match file.presets.get(name) {
+                Some(toml::Value::Table(preset)) => {
+                    layers.push((&file.path, preset));
+                    found = true;
+                }
+                Some(_) => {
+                    return Err(ThumbsdownError::InvalidConfig {
+                        path: file.path.clone(),
+                        reason: format!("preset `{name}` must be a table"),
+                    })
+                }
+                None => {}
+            }
+        }
+        if !found {
+            return Err(ThumbsdownError::UnknownPreset {
+                name: name.clone(),
+                defined: preset_names(files),
+            });
+        }
+    }
+
+    let mut fields = match serde_json::to_value(&args)? {
+        Value::Object(fields) => fields,
+        _ => unreachable!("Args serializes to a map"),
+    };
+    let defaults = fields.clone();
+    let command = Args::command();
+    // Config values that differ from the default, with the layer they came from
+    let mut given: Vec<(usize, &str)> = Vec::new();
+    for (layer, (path, settings)) in layers.into_iter().enumerate() {
+        let invalid = |reason: String| ThumbsdownError::InvalidConfig {
+            path: path.to_path_buf(),
+            reason,
+        };
+        for (key, value) in settings {
+            if !set(&mut fields, matches, &command, key, value).map_err(invalid)? {
+                continue;
+            }
+            given.retain(|&(_, earlier)| earlier != key);
+            if fields[key] == defaults[key] {
+                continue;
+            }
+            for &(earlier_layer, earlier) in &given {
+                if !conflicts(&command, key, earlier) {
+                    continue;
+                }
+                if earlier_layer == layer {
+                    return Err(invalid(format!("`{key}` conflicts with `{earlier}`")));
+                }
+                fields.insert(earlier.to_string(), defaults[earlier].clone());
+            }
+            given.retain(|&(_, earlier)| !conflicts(&command, key, earlier));
+            given.push((layer, key));
+        }
+    }
+
+    let mut merged: Args = serde_json::from_value(Value::Object(fields))?;
+    merged.videos = args.videos;
+    merged.preset = args.preset;
+    merged.print_config = args.print_config;
+    Ok(merged)
+}
+
+/// Sets one config value unless the option, or one it conflicts with, was
+/// given on the command line. Returns whether the value was set.
+fn set(
+    fields: &mut Map<String, Value>,
+    matches: &ArgMatches,
+    command: &Command,
+    key: &str,
+    value: &toml::Value,
+) -> std::result::Result<bool, String> {
+    if !fields.contains_key(key) {
+        return Err(format!("unknown setting `{key}`"));
+    }
+    let on_command_line = matches
+        .ids()
+        .filter(|id| matches.value_source(id.as_str()) == Some(ValueSource::CommandLine))
+        .any(|id| id.as_str() == key.replace('-', "_") || conflicts(command, key, id.as_str()));
+    if on_command_line {
+        return Ok(false);
+    }
+
+    let previous = fields.insert(
+        key.to_string(),
+        serde_json::to_value(value).map_err(|e| e.to_string())?,
+    );
+    // Check each value on its own so errors name the offending key
+    let checked = serde_json::from_value::<Args>(Value::Object(fields.clone()))
+        .map_err(|e| e.to_string())
+        .and_then(|args| check_ranges(&args));
+    if let Err(e) = checked {
+        if let Some(previous) = previous {
+            fields.insert(key.to_string(), previous);
+        }
+        return Err(format!("`{key}`: {e}"));
+    }
+    Ok(true)
+}
+
+/// Whether clap rejects the two options together; either may be given as a
+/// config key or an argument id.
+fn conflicts(command: &Command, a: &str, b: &str) -> bool {
+    let arg = |name: &str| {
+        let id = name.replace('-', "_");
+        command
+            .get_arguments()
+            .find(|arg| arg.get_id() == id.as_str())
+    };
+    let (Some(a), Some(b)) = (arg(a), arg(b)) else {
+        return false;
+    };
+    let rejects = |arg, other: &clap::Arg| {
+        command
+            .get_arg_conflicts_with(arg)
+            .iter()
+            .any(|conflict| conflict.get_id() == other.get_id())
+    };
+    rejects(a, b) || rejects(b, a)
+}
+
+/// Ranges clap enforces on the command line.
+fn check_ranges(args: &Args) -> std::result::Result<(), String> {
+    if args.quality.is_some_and(|q| !(1..=100).contains(&q))
+        || !(1..=100).contains(&args.bif_quality)
+    {
+        return Err("must be between 1 and 100".to_string());
+    }
+    if args.every.is_some_and(|secs| secs <= 0.0) || args.bif_interval <= 0.0 {
+        return Err("must be positive".to_string());
+    }
+    if args.bif_width < 2 {
+        return Err("must be at least 2".to_string());
+    }
+    if [
+        args.jobs,
+        args.max_thumbs,
+        args.rows_per_page,
+        args.rows,
+        args.max_height,
+        Some(args.frame_delay),
+        Some(args.burst),
+    ]
+    .contains(&Some(0))
+    {
+        return Err("must be at least 1".to_string());
+    }
+    Ok(())
+}
+
+fn preset_names(files: &[ConfigFile]) -> String {
+    let mut names: Vec<&str> = files
+        .iter()
+        .flat_map(|file| file.presets.keys().map(String::as_str))
+        .collect();
+    names.sort_unstable();
+    names.dedup();
+    if names.is_empty() {
+        "none".to_string()
+    } else {
+        names.join(", ")
+    }
+}
+
+/// The effective settings as a config file.
+pub fn to_toml(args: &Args) -> Result<String> {
+    Ok(toml::to_string(args)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use thumbsdown::layout::Columns;
+
+    use super::*;
+
+    const CONFIG: &str = r#"
+thumbs = 30
+width = 400
+
+[preset.web]
+format = "jpeg"
+quality = 75
+width = 240
+"#;
+
+    fn run(argv: &[&str], files: &[ConfigFile]) -> Result<Args> {
+        let matches = Args::command().get_matches_from(argv);
+        let args = Args::from_arg_matches(&matches).expect("args");
+        apply(args, &matches, files)
+    }
+
+    fn file(text: &str) -> ConfigFile {
+        parse(Path::new("config.toml"), text).expect("parse")
+    }
+
+    #[test]
+    fn config_overrides_defaults() {
+        let args = run(&["thumbsdown", "a.mp4"], &[file(CONFIG)]).expect("apply");
+        assert_eq!(args.thumbs, 30);
+        assert_eq!(args.width, 400);
+        assert_eq!(args.columns, Columns::Count(5));
+        assert_eq!(args.videos, vec![PathBuf::from("a.mp4")]);
+    }
+
+    #[test]
+    fn command_line_overrides_config_and_preset() {
+        let args = run(
+            &["thumbsdown", "-p", "web", "-w", "500", "-t", "9", "a.mp4"],
+            &[file(CONFIG)],
+        )
+        .expect("apply");
+        assert_eq!(args.thumbs, 9);
+        assert_eq!(args.width, 500);
+        assert_eq!(args.quality, Some(75));
+        assert_eq!(args.format, Some(thumbsdown::encode::OutputFormat::Jpeg));
+    }
+
+    #[test]
+    fn later_files_and_presets_win() {
+        let local = file("width = 100\n[preset.web]\nquality = 50\n");
+        let args = run(
+            &["thumbsdown", "-p", "web", "a.mp4"],
+            &[file(CONFIG), local],
+        )
+        .expect("apply");
+        // The preset is applied after every file's top-level settings
+        assert_eq!(args.width, 240);
+        assert_eq!(args.quality, Some(50));
+    }
+
+    #[test]
+    fn command_line_drops_conflicting_config_values() {
+        let config = file("rows = 3\nupdate = true\n");
+        let args = run(&["thumbsdown", "-c", "4", "--force", "a.mp4"], &[config]).expect("apply");
+        assert_eq!((args.columns, args.rows), (Columns::Count(4), None));
+        assert!(args.force && !args.update);
+    }
+
+    #[test]
+    fn later_layer_drops_conflicting_values() {
+        let config = file("rows = 3\n[preset.wide]\ncolumns = 8\n");
+        let args = run(&["thumbsdown", "-p", "wide", "a.mp4"], &[config]).expect("apply");
+        assert_eq!((args.columns, args.rows), (Columns::Count(8), None));
+
+        let err = run(&["thumbsdown", "a.mp4"], &[file("rows = 3\ncolumns = 8\n")]).unwrap_err();
+        assert!(err.to_string().contains("conflicts with"));
+        // A value left at its default conflicts with nothing
+        let args =
+            run(&["thumbsdown", "a.mp4"], &[file("rows = 3\ncolumns = 5\n")]).expect("apply");
+        assert_eq!(args.rows, Some(3));
+    }
+
+    #[test]
+    fn unknown_preset_lists_defined_ones() {
+        let err = run(&["thumbsdown", "-p", "archive", "a.mp4"], &[file(CONFIG)]).unwrap_err();
+        assert!(matches!(err, ThumbsdownError::UnknownPreset { .. }));
+        assert!(err.to_string().contains("defined: web"));
+    }
+
+    #[test]
+    fn unknown_key_is_rejected() {
+        let err = run(&["thumbsdown", "a.mp4"], &[file("thumb = 3\n")]).unwrap_err();
+        assert!(err.to_string().contains("unknown setting `thumb`"));
+    }
+
+    #[test]
+    fn invalid_values_name_the_key() {
+        let err = run(&["thumbsdown", "a.mp4"], &[file("columns = \"four\"\n")]).unwrap_err();
+        assert!(err.to_string().contains("`columns`"));
+
+        let err = run(&["thumbsdown", "a.mp4"], &[file("quality = 0\n")]).unwrap_err();
+        assert!(err.to_string().contains("between 1 and 100"));
+    }
+
+    #[test]
+    fn intervals_accept_durations() {
+        let config = file("every = \"1m30s\"\nbif-interval = \"0:05\"\n");
+        let args = run(&["thumbsdown", "a.mp4"], &[config]).expect("apply");
+        assert_eq!((args.every, args.bif_interval), (Some(90.0), 5.0));
+
+        let args = run(&["thumbsdown", "a.mp4"], &[file("every = 30\n")]).expect("apply");
+        assert_eq!(args.every, Some(30.0));
+
+        let err = run(&["thumbsdown", "a.mp4"], &[file("every = \"soon\"\n")]).unwrap_err();
+        assert!(err.to_string().contains("`every`"));
+    }
+
+    #[test]
+    fn enum_values_use_option_names() {
+        let args = run(
+            &["thumbsdown", "a.mp4"],
+            &[file(
+                "select = \"scenes\"\ntimestamp-position = \"top-left\"\n",
+            )],
+        )
+        .expect("apply");
+        assert_eq!(args.select, thumbsdown::select::Selection::Scenes);
+        assert_eq!(args.timestamp_position, thumbsdown::grid::Corner::TopLeft);
+    }
+
+    #[test]
+    fn printed_config_round_trips() {
+        let args = run(&["thumbsdown", "-t", "12", "--print-config"], &[]).expect("apply");
+        let text = to_toml(&args).expect("toml");
+        assert!(text.contains("thumbs = 12"));
+        let args = run(&["thumbsdown", "a.mp4"], &[file(&text)]).expect("apply");
+        assert_eq!(args.thumbs, 12);
+    }
+}
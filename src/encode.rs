@@ -0,0 +1,365 @@
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+use clap::ValueEnum;
+use image::codecs::avif::AvifEncoder;
+use image::codecs::png::PngEncoder;
+use image::{ExtendedColorType, ImageEncoder, Rgb, RgbImage, RgbaImage};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Result, ThumbsdownError};
+use crate::pdf;
+use crate::style::Color;
+
+// ravif speed 1-10; 6 trades a little size for a much shorter encode
+const AVIF_SPEED: u8 = 6;
+
+/// Image format of the written sheet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OutputFormat {
+    Png,
+    Jpeg,
+    Webp,
+    Avif,
+    /// JPEG pages in a PDF document; paged sheets share one file
+    Pdf,
+}
+
+impl OutputFormat {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        [Self::Png, Self::Jpeg, Self::Webp, Self::Avif, Self::Pdf]
+            .into_iter()
+            .find(|format| format.extensions().contains(&ext.as_str()))
+    }
+
+    pub fn extensions(&self) -> &'static [&'static str] {
+        match self {
+            Self::Png => &["png"],
+            Self::Jpeg => &["jpg", "jpeg"],
+            Self::Webp => &["webp"],
+            Self::Avif => &["avif"],
+            Self::Pdf => &["pdf"],
+        }
+    }
+
+    /// Quality used for lossy formats when none is given.
+    pub fn default_quality(&self) -> u8 {
+        match self {
+            Self::Png => 100,
+            Self::Jpeg | Self::Pdf => 90,
+            Self::Webp => 80,
+            Self::Avif => 70,
+        }
+    }
+}
+
+/// Encoder settings for the written sheet.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct EncodeOptions {
+    /// Format to write; inferred from the output extension when `None`
+    pub format: Option<OutputFormat>,
+    /// Quality 1-100 for JPEG, lossy WebP and AVIF
+    pub quality: Option<u8>,
+    /// Write progressive JPEG
+    pub progressive: bool,
+    /// Write lossless WebP
+    pub lossless: bool,
+    /// Color transparent areas are flattened onto for formats without alpha
+    /// (JPEG and PDF); white when `None`
+    pub flatten: Option<Color>,
+}
+
+/// Picks the output format for `path`, failing when the requested format
+/// disagrees with the extension or neither identifies a supported format.
+pub fn resolve_format(path: &Path, requested: Option<OutputFormat>) -> Result<OutputFormat> {
+    match (requested, OutputFormat::from_path(path)) {
+        (Some(format), Some(detected)) if format != detected => {
+            Err(ThumbsdownError::FormatMismatch {
+                path: path.to_path_buf(),
+                format: format!("{format:?}").to_lowercase(),
+            })
+        }
+        (Some(format), _) | (None, Some(format)) => Ok(format),
+        (None, None) => Err(ThumbsdownError::UnsupportedFormat(path.to_path_buf())),
+    }
+}
+
+impl EncodeOptions {
+    fn quality_for(&self, format: OutputFormat) -> u8 {
+        self.quality
+            .unwrap_or_else(|| format.default_quality())
+            .clamp(1, 100)
+    }
+
+    fn flatten_color(&self) -> Color {
+        self.flatten.unwrap_or(Color::WHITE)
+    }
+}
+
+/// Writes `image` in the format picked by `resolve_format`. Formats with
+/// alpha keep it when the image has transparent pixels; the others get the
+/// image flattened onto `options.flatten`.
+pub fn save(image: &RgbaImage, path: &Path, options: &EncodeOptions) -> Result<()> {
+    let format = resolve_format(path, options.format)?;
+    let quality = options.quality_for(format);
+    let (width, height) = image.dimensions();
+
+    match format {
+        OutputFormat::Png => {
+            let (data, color_type) = pixel_data(image, options);
+            let writer = BufWriter::new(File::create(path)?);
+            PngEncoder::new(writer).write_image(&data, width, height, color_type)?;
+        }
+        OutputFormat::Jpeg => {
+            let image = flatten(image, options.flatten_color());
+            let data = encode_jpeg(&image, quality, options.progressive)?;
+            std::fs::write(path, data)?;
+        }
+        OutputFormat::Webp => {
+            let (data, color_type) = pixel_data(image, options);
+            let encoder = match color_type {
+                ExtendedColorType::Rgba8 => webp::Encoder::from_rgba(&data, width, height),
+                _ => webp::Encoder::from_rgb(&data, width, height),
+            };
+            let data = encoder
+                .encode_simple(options.lossless, quality as f32)
+                .map_err(|e| ThumbsdownError::Encode(format!("webp: {e:?}")))?;
+            std::fs::write(path, &*data)?;
+        }
+        OutputFormat::Avif => {
+            let (data, color_type) = pixel_data(image, options);
+            let writer = BufWriter::new(File::create(path)?);
+            AvifEncoder::new_with_speed_quality(writer, AVIF_SPEED, quality)
+                .write_image(&data, width, height, color_type)?;
+        }
+        OutputFormat::Pdf => save_pdf(std::slice::from_ref(image), path, options)?,
+    }
+
+    Ok(())
+}
+
+/// Writes the images as the pages of one PDF, flattened onto
+/// `options.flatten`.
+pub fn save_pdf(pages: &[RgbaImage], path: &Path, options: &EncodeOptions) -> Result<()> {
+    let quality = options.quality_for(OutputFormat::Pdf);
+    let pages = pages
+        .iter()
+        .map(|image| {
+            let image = flatten(image, options.flatten_color());
+            Ok(pdf::JpegPage {
+                width: image.width(),
+                height: image.height(),
+                // Baseline keeps the pages readable by older PDF viewers
+                data: encode_jpeg(&image, quality, false)?,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    pdf::write(BufWriter::new(File::create(path)?), &pages)?;
+    Ok(())
+}
+
+/// Pixels of `image` for an encoder that takes RGB or RGBA: RGBA when the
+/// image has transparent pixels, else flattened RGB to keep the file small.
+fn pixel_data<'a>(
+    image: &'a RgbaImage,
+    options: &EncodeOptions,
+) -> (Cow<'a, [u8]>, ExtendedColorType) {
+    if image.pixels().all(|pixel| pixel.0[3] == u8::MAX) {
+        let rgb = flatten(image, options.flatten_color());
+        (Cow::Owned(rgb.into_raw()), ExtendedColorType::Rgb8)
+    } else {
+        (Cow::Borrowed(image.as_raw()), ExtendedColorType::Rgba8)
+    }
+}
+
+/// Blends `image` over a solid `background`, dropping the alpha channel. The
+/// background's own alpha is ignored.
+pub fn flatten(image: &RgbaImage, background: Color) -> RgbImage {
+    let [br, bg, bb, _] = background.0;
+    RgbImage::from_fn(image.width(), image.height(), |x, y| {
+        let [r, g, b, a] = image.get_pixel(x, y).0;
+        let mix = |over: u8, under: u8| {
+            ((over as u32 * a as u32 + under as u32 * (255 - a as u32) + 127) / 255) as u8
+        };
+        Rgb([mix(r, br), mix(g, bg), mix(b, bb)])
+    })
+}
+
+pub(crate) fn encode_jpeg(image: &RgbImage, quality: u8, progressive: bool) -> Result<Vec<u8>> {
+    let (width, height) = image.dimensions();
+    let (w, h) = match (u16::try_from(width), u16::try_from(height)) {
+        (Ok(w), Ok(h)) => (w, h),
+        _ => {
+            return Err(ThumbsdownError::Encode(format!(
+                "{width}x{height} exceeds the JPEG limit of 65535 pixels per side"
+            )))
+        }
+    };
+    let mut data = Vec::new();
+    let mut encoder = jpeg_encoder::Encoder::new(&mut data, quality);
+    encoder.set_progressive(progressive);
+    encoder.set_optimized_huffman_tables(true);
+    encoder
+        .encode(image, w, h, jpeg_encoder::ColorType::Rgb)
+        .map_err(|e| ThumbsdownError::Encode(e.to_string()))?;
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgba;
+
+    use super::*;
+
+    fn test_image() -> RgbaImage {
+        RgbaImage::from_fn(64, 48, |x, y| {
+            Rgba([(x * 4) as u8, (y * 5) as u8, 128, 255])
+        })
+    }
+
+    /// `test_image` with its left half fully transparent.
+    fn transparent_image() -> RgbaImage {
+        let mut image = test_image();
+        for (x, _, pixel) in image.enumerate_pixels_mut() {
+            if x < 32 {
+                pixel.0[3] = 0;
+            }
+        }
+        image
+    }
+
+    #[test]
+    fn from_path_detects_extensions() {
+        assert_eq!(
+            OutputFormat::from_path(Path::new("a.JPG")),
+            Some(OutputFormat::Jpeg)
+        );
+        assert_eq!(
+            OutputFormat::from_path(Path::new("a.webp")),
+            Some(OutputFormat::Webp)
+        );
+        assert_eq!(OutputFormat::from_path(Path::new("a.gif")), None);
+        assert_eq!(OutputFormat::from_path(Path::new("noext")), None);
+    }
+
+    #[test]
+    fn resolve_format_rejects_mismatch() {
+        let err = resolve_format(Path::new("out.png"), Some(OutputFormat::Jpeg)).unwrap_err();
+        assert!(matches!(err, ThumbsdownError::FormatMismatch { .. }));
+    }
+
+    #[test]
+    fn resolve_format_allows_unknown_extension_with_explicit_format() {
+        let format =
+            resolve_format(Path::new("out.img"), Some(OutputFormat::Webp)).expect("format");
+        assert_eq!(format, OutputFormat::Webp);
+    }
+
+    #[test]
+    fn resolve_format_requires_known_extension() {
+        let err = resolve_format(Path::new("out.img"), None).unwrap_err();
+        assert!(matches!(err, ThumbsdownError::UnsupportedFormat(_)));
+    }
+
+    #[test]
+    fn save_writes_every_format() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        for (name, magic) in [
+            ("out.png", &b"\x89PNG"[..]),
+            ("out.jpg", &b"\xFF\xD8"[..]),
+            ("out.webp", &b"RIFF"[..]),
+        ] {
+            let path = dir.path().join(name);
+            save(&test_image(), &path, &EncodeOptions::default()).expect("save");
+            let data = std::fs::read(&path).expect("read");
+            assert!(data.starts_with(magic), "{name}");
+        }
+    }
+
+    #[test]
+    fn save_pdf_writes_every_page() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("out.pdf");
+        save_pdf(
+            &[test_image(), test_image()],
+            &path,
+            &EncodeOptions::default(),
+        )
+        .expect("save");
+        let data = std::fs::read(&path).expect("read");
+        assert!(data.starts_with(b"%PDF"));
+        assert!(data.windows(8).any(|w| w == b"/Count 2"));
+    }
+
+    #[test]
+    fn save_writes_avif() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("out.avif");
+        save(&test_image(), &path, &EncodeOptions::default()).expect("save");
+        let data = std::fs::read(&path).expect("read");
+        assert_eq!(&data[4..8], b"ftyp");
+    }
+
+    #[test]
+    fn jpeg_quality_affects_size() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let low = dir.path().join("low.jpg");
+        let high = dir.path().join("high.jpg");
+        let options = |quality| EncodeOptions {
+            quality: Some(quality),
+            progressive: true,
+            ..Default::default()
+        };
+        save(&test_image(), &low, &options(20)).expect("save");
+        save(&test_image(), &high, &options(95)).expect("save");
+        let size = |p: &Path| std::fs::metadata(p).expect("metadata").len();
+        assert!(size(&low) < size(&high));
+    }
+
+    #[test]
+    fn flatten_blends_onto_background() {
+        let image = RgbaImage::from_pixel(1, 1, Rgba([255, 0, 0, 128]));
+        let flat = flatten(&image, Color::BLACK);
+        assert_eq!(*flat.get_pixel(0, 0), Rgb([128, 0, 0]));
+        let opaque = RgbaImage::from_pixel(1, 1, Rgba([1, 2, 3, 255]));
+        assert_eq!(
+            *flatten(&opaque, Color::BLACK).get_pixel(0, 0),
+            Rgb([1, 2, 3])
+        );
+    }
+
+    #[test]
+    fn save_keeps_alpha_only_where_supported() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let options = EncodeOptions {
+            flatten: Some(Color::rgb(0, 0, 255)),
+            ..Default::default()
+        };
+        for (name, alpha) in [
+            ("opaque.png", false),
+            ("alpha.png", true),
+            ("alpha.webp", true),
+            ("alpha.jpg", false),
+        ] {
+            let path = dir.path().join(name);
+            let image = match name {
+                "opaque.png" => test_image(),
+                _ => transparent_image(),
+            };
+            save(&image, &path, &options).expect("save");
+            let decoded = image::open(&path).expect("decode");
+            assert_eq!(decoded.color().has_alpha(), alpha, "{name}");
+            if name == "alpha.jpg" {
+                let pixel = decoded.to_rgb8().get_pixel(4, 24).0;
+                assert!(
+                    pixel[2] > 200 && pixel[0] < 50,
+                    "flattened onto blue: {pixel:?}"
+                );
+            }
+        }
+    }
+}
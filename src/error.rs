@@ -10,15 +10,47 @@ pub enum ThumbsdownError {
     #[error("output file already exists: {0} (use -f to overwrite)")]
     OutputExists(PathBuf),
 
+    #[error("no video files found in the given inputs")]
+    NoInputs,
+
+    #[error("multiple inputs need an --output template such as {{dir}}/{{stem}}.thumbs.png")]
+    OutputNotTemplate,
+
+    #[error("multiple inputs need a --bif template such as {{dir}}/{{stem}}.bif")]
+    BifNotTemplate,
+
+    #[error("multiple inputs need an --animate template such as {{dir}}/{{stem}}.preview.gif")]
+    AnimateNotTemplate,
+
+    #[error("multiple inputs need a --manifest template such as {{dir}}/{{stem}}.thumbs.json")]
+    ManifestNotTemplate,
+
+    #[error("unknown output template placeholder: {{{0}}}")]
+    UnknownOutputPlaceholder(String),
+
     #[error("temp directory does not exist: {0}")]
     TempDirNotFound(PathBuf),
 
     #[error("keep-frames directory does not exist: {0}")]
     KeepFramesDirNotFound(PathBuf),
 
-    #[error("--no-grid requires --keep-frames (otherwise nothing is produced)")]
+    #[error(
+        "--no-grid requires --keep-frames, --bif or --animate (otherwise nothing is produced)"
+    )]
     NoGridWithoutKeepFrames,
 
+    #[error("--update with --no-grid needs --bif or --animate (kept frames are not tracked)")]
+    UpdateWithoutOutputs,
+
+    #[error("--vtt needs an image output such as sprite.jpg, not a PDF")]
+    VttNeedsImage,
+
+    #[error("invalid config file {path}: {reason}")]
+    InvalidConfig { path: PathBuf, reason: String },
+
+    #[error("unknown preset: {name} (defined: {defined})")]
+    UnknownPreset { name: String, defined: String },
+
     #[error("ffprobe not found on PATH (install ffmpeg)")]
     FfprobeNotFound,
 
@@ -34,9 +66,65 @@ pub enum ThumbsdownError {
     #[error("ffmpeg frame capture failed at {time}s: {reason}")]
     FrameCaptureFailed { time: f64, reason: String },
 
+    #[error("ffmpeg scene detection failed: {0}")]
+    SceneDetectionFailed(String),
+
+    #[error("invalid time: {0} (use seconds, [HH:]MM:SS[.mmm], a percentage or a negative offset from the end)")]
+    InvalidTime(String),
+
+    #[error("invalid interval: {0} (use e.g. 30s, 2m, 1h30m or 1:30)")]
+    InvalidInterval(String),
+
+    #[error("time {time} is outside the video (duration {duration:.3}s)")]
+    TimeOutOfRange { time: String, duration: f64 },
+
+    #[error("start time {start} is not before end time {end}")]
+    EmptyTimeRange { start: String, end: String },
+
+    #[error(
+        "invalid color: {0} (use #rrggbb, #rrggbbaa, #rgb or white, black, gray or transparent)"
+    )]
+    InvalidColor(String),
+
+    #[error("invalid sheet size: {0} (use WIDTHxHEIGHT such as 1920x1080, or a width)")]
+    InvalidSheetSize(String),
+
+    #[error("invalid column count: {0} (use a number or auto)")]
+    InvalidColumns(String),
+
+    #[error("invalid aspect ratio: {0} (use W:H such as 16:9, or a ratio such as 2.39)")]
+    InvalidAspect(String),
+
+    #[error("sheet size {0} is too small for the thumbnails, header and spacing")]
+    SheetTooSmall(String),
+
+    #[error("unknown header template placeholder: {{{0}}}")]
+    UnknownPlaceholder(String),
+
+    #[error("header template is empty; use --no-header to omit the header")]
+    EmptyHeaderTemplate,
+
+    #[error("unterminated header template placeholder: {{{0}")]
+    UnterminatedPlaceholder(String),
+
+    #[error("output file {path} does not match --format {format}")]
+    FormatMismatch { path: PathBuf, format: String },
+
+    #[error("unsupported output format: {0} (use png, jpg, webp, avif or pdf, or pass --format)")]
+    UnsupportedFormat(PathBuf),
+
+    #[error("unsupported animation format: {0} (use gif, webp, png or apng)")]
+    UnsupportedAnimationFormat(PathBuf),
+
+    #[error("image encoding error: {0}")]
+    Encode(String),
+
     #[error("image processing error: {0}")]
     ImageError(#[from] image::ImageError),
 
+    #[error("thread pool error: {0}")]
+    ThreadPool(#[from] rayon::ThreadPoolBuildError),
+
     #[error("font loading error: {0}")]
     FontError(String),
 
@@ -45,6 +133,9 @@ pub enum ThumbsdownError {
 
     #[error("json parse error: {0}")]
     Json(#[from] serde_json::Error),
+
+    #[error("toml error: {0}")]
+    Toml(#[from] toml::ser::Error),
 }
 
 pub type Result<T> = std::result::Result<T, ThumbsdownError>;
@@ -66,6 +157,12 @@ mod tests {
         assert!(err.to_string().contains("-f"));
     }
 
+    #[test]
+    fn unknown_placeholder_names_it() {
+        let err = ThumbsdownError::UnknownPlaceholder("nope".to_string());
+        assert!(err.to_string().contains("{nope}"));
+    }
+
     #[test]
     fn ffprobe_not_found_suggests_install() {
         let err = ThumbsdownError::FfprobeNotFound;
@@ -0,0 +1,193 @@
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use serde::{Deserialize, Serialize};
+
+use crate::batch;
+use crate::error::Result;
+
+/// What an output was generated from: the input file's size and mtime plus
+/// the generation parameters. Stored in a sidecar next to the output.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Fingerprint {
+    pub input_size: u64,
+    pub input_mtime_secs: u64,
+    pub input_mtime_nanos: u32,
+    pub params: serde_json::Value,
+}
+
+impl Fingerprint {
+    pub fn compute(input: &Path, params: &impl Serialize) -> Result<Self> {
+        let metadata = std::fs::metadata(input)?;
+        let mtime = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        Ok(Self {
+            input_size: metadata.len(),
+            input_mtime_secs: mtime.as_secs(),
+            input_mtime_nanos: mtime.subsec_nanos(),
+            params: serde_json::to_value(params)?,
+        })
+    }
+
+    pub fn read(output: &Path) -> Option<Self> {
+        let data = std::fs::read(sidecar_path(output)).ok()?;
+        serde_json::from_slice(&data).ok()
+    }
+
+    pub fn write(&self, output: &Path) -> Result<()> {
+        std::fs::write(sidecar_path(output), serde_json::to_vec_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+/// `out.png` -> `out.png.thumbsdown.json`
+pub fn sidecar_path(output: &Path) -> PathBuf {
+    let mut name = output.as_os_str().to_owned();
+    name.push(".thumbsdown.json");
+    PathBuf::from(name)
+}
+
+/// Whether the `outputs` written from `input` can be reused. Each must exist
+/// (or its first page), and the fingerprint in the first output's sidecar
+/// must match exactly; outputs without one are trusted if all are newer
+/// than the input.
+pub fn is_up_to_date(input: &Path, outputs: &[PathBuf], fingerprint: &Fingerprint) -> bool {
+    let Some(output) = outputs.first() else {
+        return false;
+    };
+    let Some(existing) = outputs
+        .iter()
+        .map(|output| batch::existing_output(output))
+        .collect::<Option<Vec<PathBuf>>>()
+    else {
+        return false;
+    };
+    if let Some(stored) = Fingerprint::read(output) {
+        return stored == *fingerprint;
+    }
+    if sidecar_path(output).exists() {
+        // Unreadable sidecar: regenerate rather than guess
+        return false;
+    }
+
+    let modified = |p: &Path| std::fs::metadata(p).and_then(|m| m.modified()).ok();
+    let Some(input) = modified(input) else {
+        return false;
+    };
+    existing
+        .iter()
+        .all(|output| modified(output).is_some_and(|output| output > input))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use serde_json::json;
+
+    use super::*;
+
+    fn set_mtime(path: &Path, time: SystemTime) {
+        let file = std::fs::File::options()
+            .write(true)
+            .open(path)
+            .expect("open");
+        file.set_modified(time).expect("set mtime");
+    }
+
+    #[test]
+    fn sidecar_path_appends_suffix() {
+        assert_eq!(
+            sidecar_path(Path::new("dir/out.png")),
+            PathBuf::from("dir/out.png.thumbsdown.json")
+        );
+    }
+
+    #[test]
+    fn matching_fingerprint_is_up_to_date() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let input = dir.path().join("video.mp4");
+        let output = dir.path().join("out.png");
+        std::fs::write(&input, b"video").expect("write");
+        std::fs::write(&output, b"image").expect("write");
+        let outputs = [output.clone()];
+
+        let fingerprint = Fingerprint::compute(&input, &json!({"thumbs": 20})).expect("compute");
+        fingerprint.write(&output).expect("write sidecar");
+        assert!(is_up_to_date(&input, &outputs, &fingerprint));
+
+        let changed = Fingerprint::compute(&input, &json!({"thumbs": 12})).expect("compute");
+        assert!(!is_up_to_date(&input, &outputs, &changed));
+    }
+
+    #[test]
+    fn changed_input_is_stale() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let input = dir.path().join("video.mp4");
+        let output = dir.path().join("out.png");
+        std::fs::write(&input, b"video").expect("write");
+        std::fs::write(&output, b"image").expect("write");
+        let outputs = [output.clone()];
+
+        let params = json!({"thumbs": 20});
+        Fingerprint::compute(&input, &params)
+            .expect("compute")
+            .write(&output)
+            .expect("write sidecar");
+
+        std::fs::write(&input, b"re-encoded video").expect("write");
+        let fingerprint = Fingerprint::compute(&input, &params).expect("compute");
+        assert!(!is_up_to_date(&input, &outputs, &fingerprint));
+    }
+
+    #[test]
+    fn missing_output_is_stale() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let input = dir.path().join("video.mp4");
+        std::fs::write(&input, b"video").expect("write");
+
+        let fingerprint = Fingerprint::compute(&input, &json!({})).expect("compute");
+        let outputs = [dir.path().join("out.png")];
+        assert!(!is_up_to_date(&input, &outputs, &fingerprint));
+        assert!(!is_up_to_date(&input, &[], &fingerprint));
+    }
+
+    #[test]
+    fn without_sidecar_falls_back_to_mtime() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let input = dir.path().join("video.mp4");
+        let output = dir.path().join("out.png");
+        std::fs::write(&input, b"video").expect("write");
+        std::fs::write(&output, b"image").expect("write");
+        let outputs = [output.clone()];
+        let fingerprint = Fingerprint::compute(&input, &json!({})).expect("compute");
+
+        let now = SystemTime::now();
+        set_mtime(&input, now - Duration::from_secs(60));
+        set_mtime(&output, now);
+        assert!(is_up_to_date(&input, &outputs, &fingerprint));
+
+        set_mtime(&output, now - Duration::from_secs(120));
+        assert!(!is_up_to_date(&input, &outputs, &fingerprint));
+    }
+
+    #[test]
+    fn missing_extra_output_is_stale() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let input = dir.path().join("video.mp4");
+        let output = dir.path().join("out.png");
+        let manifest = dir.path().join("out.json");
+        std::fs::write(&input, b"video").expect("write");
+        std::fs::write(&output, b"image").expect("write");
+
+        let fingerprint = Fingerprint::compute(&input, &json!({})).expect("compute");
+        fingerprint.write(&output).expect("write sidecar");
+        let outputs = [output, manifest.clone()];
+        assert!(!is_up_to_date(&input, &outputs, &fingerprint));
+
+        std::fs::write(&manifest, b"{}").expect("write");
+        assert!(is_up_to_date(&input, &outputs, &fingerprint));
+    }
+}
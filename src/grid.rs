@@ -1,73 +1,257 @@
-use std::path::Path;
-
+use ab_glyph::{FontRef, PxScale};
+use clap::ValueEnum;
 use image::imageops::FilterType;
-use image::{DynamicImage, GenericImageView, Rgb, RgbImage};
+use image::{DynamicImage, GenericImageView, Pixel, RgbImage, Rgba, RgbaImage};
+use imageproc::drawing::{draw_text_mut, text_size};
+use serde::{Deserialize, Serialize};
 
 use crate::error::Result;
+use crate::header;
+use crate::style::{Color, Style};
+use crate::timecode;
 
-const BORDER_COLOR: Rgb<u8> = Rgb([255, 255, 255]);
-const BG_COLOR: Rgb<u8> = Rgb([255, 255, 255]);
+const LABEL_TEXT_COLOR: Rgba<u8> = Rgba([255, 255, 255, 255]);
+// Black at 60% opacity
+const LABEL_BOX_COLOR: Rgba<u8> = Rgba([0, 0, 0, 153]);
+const LABEL_PADDING: u32 = 3;
+const LABEL_INSET: u32 = 4;
 
-pub fn process_thumbnail(path: &Path, target_width: u32, border_size: u32) -> Result<DynamicImage> {
-    let img = image::open(path)?;
-    let bordered = add_border(&img, border_size);
-    let resized = bordered.resize(target_width, u32::MAX, FilterType::Lanczos3);
-    Ok(resized)
+/// A processed thumbnail and where in the video it was captured.
+#[derive(Debug, Clone)]
+pub struct Thumbnail {
+    pub image: DynamicImage,
+    /// Capture time in seconds
+    pub time: f64,
+    /// Frame number at the capture time
+    pub frame: u64,
 }
 
-pub fn compose_grid(thumbnails: &[DynamicImage], columns: u32) -> RgbImage {
-    let cols = columns as usize;
-    let row_images: Vec<RgbImage> = thumbnails.chunks(cols).map(concat_horizontal).collect();
+/// Corner of a thumbnail a label is drawn in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    #[default]
+    BottomRight,
+}
 
-    concat_vertical(&row_images)
+/// Settings for the per-thumbnail timestamp label.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct TimestampOverlay {
+    pub corner: Corner,
+    pub font_size: f32,
+    /// Append the frame number to the timestamp
+    pub frame_numbers: bool,
+}
+
+impl Default for TimestampOverlay {
+    fn default() -> Self {
+        Self {
+            corner: Corner::default(),
+            font_size: 14.0,
+            frame_numbers: false,
+        }
+    }
 }
 
-pub fn assemble_final(header: &RgbImage, grid: &RgbImage) -> RgbImage {
-    let width = header.width().max(grid.width());
-    let height = header.height() + grid.height();
-    let mut result = RgbImage::from_pixel(width, height, BG_COLOR);
+impl TimestampOverlay {
+    pub fn text(&self, thumb: &Thumbnail) -> String {
+        let time = timecode::format_hms(thumb.time);
+        if self.frame_numbers {
+            format!("{time} #{}", thumb.frame)
+        } else {
+            time
+        }
+    }
+}
+
+/// Scales `frame` so that, with the style's border around it, the thumbnail
+/// is `target_width` pixels wide. The aspect ratio is kept, so frames should
+/// come at the video's display size (see `VideoInfo::display_size`) to show
+/// rotated and anamorphic video the way players do.
+pub fn process_thumbnail(frame: &DynamicImage, target_width: u32, style: &Style) -> DynamicImage {
+    let border = style.border_width;
+    let inner_width = target_width.saturating_sub(border * 2).max(1);
+    let resized = frame.resize(inner_width, u32::MAX, FilterType::Lanczos3);
+    add_border(&resized, border, style.border_color)
+}
 
-    let header_x = (width.saturating_sub(header.width())) as i64 / 2;
-    image::imageops::overlay(&mut result, header, header_x, 0);
+/// Crops the border `process_thumbnail` drew around a thumbnail.
+pub fn strip_border(thumbnail: &DynamicImage, border_width: u32) -> RgbImage {
+    let (w, h) = thumbnail.dimensions();
+    let border = border_width
+        .min(w.saturating_sub(1) / 2)
+        .min(h.saturating_sub(1) / 2);
+    thumbnail
+        .crop_imm(border, border, w - border * 2, h - border * 2)
+        .to_rgb8()
+}
+
+pub fn compose_grid(
+    thumbnails: &[Thumbnail],
+    columns: u32,
+    overlay: Option<&TimestampOverlay>,
+    style: &Style,
+) -> Result<RgbaImage> {
+    let images: Vec<DynamicImage> = match overlay {
+        Some(overlay) => {
+            let font = header::font()?;
+            thumbnails
+                .iter()
+                .map(|thumb| {
+                    let mut image = thumb.image.to_rgba8();
+                    draw_label(&mut image, &overlay.text(thumb), overlay, &font);
+                    DynamicImage::ImageRgba8(image)
+                })
+                .collect()
+        }
+        None => thumbnails.iter().map(|thumb| thumb.image.clone()).collect(),
+    };
+
+    let cols = columns.max(1) as usize;
+    let row_images: Vec<RgbaImage> = images
+        .chunks(cols)
+        .map(|row| concat_horizontal(row, style.gutter, style.background))
+        .collect();
+
+    Ok(concat_vertical(&row_images, style.gutter, style.background))
+}
+
+/// Where `compose_grid` places each thumbnail, and the size of the grid.
+pub fn layout(
+    thumbnails: &[Thumbnail],
+    columns: u32,
+    style: &Style,
+) -> (Vec<(u32, u32)>, (u32, u32)) {
+    let gutter = style.gutter;
+    let mut offsets = Vec::with_capacity(thumbnails.len());
+    let (mut width, mut y) = (0, 0);
+    for (i, row) in thumbnails.chunks(columns.max(1) as usize).enumerate() {
+        if i > 0 {
+            y += gutter;
+        }
+        let mut x = 0;
+        for (j, thumb) in row.iter().enumerate() {
+            if j > 0 {
+                x += gutter;
+            }
+            offsets.push((x, y));
+            x += thumb.image.width();
+        }
+        width = width.max(x);
+        y += row
+            .iter()
+            .map(|thumb| thumb.image.height())
+            .max()
+            .unwrap_or(0);
+    }
+    (offsets, (width, y))
+}
 
-    let grid_x = (width.saturating_sub(grid.width())) as i64 / 2;
-    image::imageops::overlay(&mut result, grid, grid_x, header.height() as i64);
+/// Stacks the header, if any, above the grid inside the style's margin. The
+/// header is centred on a band of the header background across the sheet.
+/// Parts are copied rather than blended, so translucent colors come out as
+/// given instead of stacking up.
+pub fn assemble_final(header: Option<&RgbaImage>, grid: &RgbaImage, style: &Style) -> RgbaImage {
+    let header_size = header.map_or((0, 0), |header| header.dimensions());
+    let margin = style.margin;
+    let width = header_size.0.max(grid.width()) + margin * 2;
+    let height = header_size.1 + grid.height() + margin * 2;
+    let mut result = RgbaImage::from_pixel(width, height, style.background.rgba());
+
+    if let Some(header) = header {
+        let band = RgbaImage::from_pixel(width, header.height(), style.header_background.rgba());
+        image::imageops::replace(&mut result, &band, 0, margin as i64);
+        let header_x = (width - header.width()) as i64 / 2;
+        image::imageops::replace(&mut result, header, header_x, margin as i64);
+    }
+
+    let (grid_x, grid_y) = grid_origin(header_size, grid.dimensions(), style);
+    image::imageops::replace(&mut result, grid, grid_x as i64, grid_y as i64);
 
     result
 }
 
-fn add_border(img: &DynamicImage, border: u32) -> DynamicImage {
+/// Top-left corner of the grid in `assemble_final`'s output: centred below
+/// the header, inside the margin.
+pub fn grid_origin(header: (u32, u32), grid: (u32, u32), style: &Style) -> (u32, u32) {
+    let width = header.0.max(grid.0);
+    (style.margin + (width - grid.0) / 2, style.margin + header.1)
+}
+
+/// Draws `text` on a semi-transparent box in the overlay's corner.
+fn draw_label(image: &mut RgbaImage, text: &str, overlay: &TimestampOverlay, font: &FontRef) {
+    let scale = PxScale::from(overlay.font_size);
+    let (text_w, text_h) = text_size(scale, font, text);
+    let box_w = (text_w + LABEL_PADDING * 2).min(image.width());
+    let box_h = (text_h + LABEL_PADDING * 2).min(image.height());
+
+    let right = image.width().saturating_sub(box_w + LABEL_INSET);
+    let bottom = image.height().saturating_sub(box_h + LABEL_INSET);
+    let (x, y) = match overlay.corner {
+        Corner::TopLeft => (LABEL_INSET, LABEL_INSET),
+        Corner::TopRight => (right, LABEL_INSET),
+        Corner::BottomLeft => (LABEL_INSET, bottom),
+        Corner::BottomRight => (right, bottom),
+    };
+
+    for py in y..(y + box_h).min(image.height()) {
+        for px in x..(x + box_w).min(image.width()) {
+            image.get_pixel_mut(px, py).blend(&LABEL_BOX_COLOR);
+        }
+    }
+
+    draw_text_mut(
+        image,
+        LABEL_TEXT_COLOR,
+        (x + LABEL_PADDING) as i32,
+        (y + LABEL_PADDING) as i32,
+        scale,
+        font,
+        text,
+    );
+}
+
+fn add_border(img: &DynamicImage, border: u32, color: Color) -> DynamicImage {
     let (w, h) = img.dimensions();
     let new_w = w + border * 2;
     let new_h = h + border * 2;
-    let mut bordered = RgbImage::from_pixel(new_w, new_h, BORDER_COLOR);
-    image::imageops::overlay(&mut bordered, &img.to_rgb8(), border as i64, border as i64);
-    DynamicImage::ImageRgb8(bordered)
+    let mut bordered = RgbaImage::from_pixel(new_w, new_h, color.rgba());
+    image::imageops::replace(&mut bordered, &img.to_rgba8(), border as i64, border as i64);
+    DynamicImage::ImageRgba8(bordered)
+}
+
+/// Total length of `sizes` laid end to end with `gutter` between them.
+fn span(sizes: impl ExactSizeIterator<Item = u32>, gutter: u32) -> u32 {
+    let gaps = sizes.len().saturating_sub(1) as u32 * gutter;
+    sizes.sum::<u32>() + gaps
 }
 
-fn concat_horizontal(images: &[DynamicImage]) -> RgbImage {
-    let total_width: u32 = images.iter().map(|img| img.width()).sum();
+fn concat_horizontal(images: &[DynamicImage], gutter: u32, background: Color) -> RgbaImage {
+    let total_width = span(images.iter().map(|img| img.width()), gutter);
     let max_height: u32 = images.iter().map(|img| img.height()).max().unwrap_or(0);
-    let mut result = RgbImage::from_pixel(total_width, max_height, BG_COLOR);
+    let mut result = RgbaImage::from_pixel(total_width, max_height, background.rgba());
 
     let mut x_offset: i64 = 0;
     for img in images {
-        image::imageops::overlay(&mut result, &img.to_rgb8(), x_offset, 0);
-        x_offset += img.width() as i64;
+        image::imageops::replace(&mut result, &img.to_rgba8(), x_offset, 0);
+        x_offset += (img.width() + gutter) as i64;
     }
 
     result
 }
 
-fn concat_vertical(images: &[RgbImage]) -> RgbImage {
+fn concat_vertical(images: &[RgbaImage], gutter: u32, background: Color) -> RgbaImage {
     let max_width: u32 = images.iter().map(|img| img.width()).max().unwrap_or(0);
-    let total_height: u32 = images.iter().map(|img| img.height()).sum();
-    let mut result = RgbImage::from_pixel(max_width, total_height, BG_COLOR);
+    let total_height = span(images.iter().map(|img| img.height()), gutter);
+    let mut result = RgbaImage::from_pixel(max_width, total_height, background.rgba());
 
     let mut y_offset: i64 = 0;
     for img in images {
-        image::imageops::overlay(&mut result, img, 0, y_offset);
-        y_offset += img.height() as i64;
+        image::imageops::replace(&mut result, img, 0, y_offset);
+        y_offset += (img.height() + gutter) as i64;
     }
 
     result
@@ -75,16 +259,27 @@ fn concat_vertical(images: &[RgbImage]) -> RgbImage {
 
 #[cfg(test)]
 mod tests {
+    use image::Rgb;
+
     use super::*;
+    use crate::style::Theme;
 
     fn make_test_image(w: u32, h: u32, color: Rgb<u8>) -> DynamicImage {
         DynamicImage::ImageRgb8(RgbImage::from_pixel(w, h, color))
     }
 
+    fn make_thumbnail(w: u32, h: u32, time: f64) -> Thumbnail {
+        Thumbnail {
+            image: make_test_image(w, h, Rgb([50, 50, 50])),
+            time,
+            frame: (time * 25.0) as u64,
+        }
+    }
+
     #[test]
     fn add_border_increases_dimensions() {
         let img = make_test_image(10, 10, Rgb([128, 128, 128]));
-        let bordered = add_border(&img, 5);
+        let bordered = add_border(&img, 5, Color::WHITE);
         assert_eq!(bordered.width(), 20);
         assert_eq!(bordered.height(), 20);
     }
@@ -92,7 +287,7 @@ mod tests {
     #[test]
     fn add_border_has_white_edges() {
         let img = make_test_image(10, 10, Rgb([0, 0, 0]));
-        let bordered = add_border(&img, 5);
+        let bordered = add_border(&img, 5, Color::WHITE);
         let rgb = bordered.to_rgb8();
         assert_eq!(*rgb.get_pixel(0, 0), Rgb([255, 255, 255]));
         assert_eq!(*rgb.get_pixel(19, 19), Rgb([255, 255, 255]));
@@ -101,58 +296,185 @@ mod tests {
     #[test]
     fn add_border_preserves_content() {
         let img = make_test_image(4, 4, Rgb([100, 100, 100]));
-        let bordered = add_border(&img, 2);
+        let bordered = add_border(&img, 2, Color::WHITE);
         let rgb = bordered.to_rgb8();
         assert_eq!(*rgb.get_pixel(2, 2), Rgb([100, 100, 100]));
         assert_eq!(*rgb.get_pixel(5, 5), Rgb([100, 100, 100]));
     }
 
+    #[test]
+    fn process_thumbnail_resizes_to_target_width() {
+        let frame = make_test_image(160, 90, Rgb([0, 0, 0]));
+        let thumb = process_thumbnail(&frame, 80, &Style::default());
+        assert_eq!(thumb.width(), 80);
+        // 76x43 frame inside a 2px border
+        assert_eq!(thumb.height(), 47);
+    }
+
+    #[test]
+    fn strip_border_removes_border() {
+        let frame = make_test_image(100, 50, Rgb([200, 0, 0]));
+        let style = Style {
+            border_width: 5,
+            border_color: Color::BLACK,
+            ..Style::default()
+        };
+        let thumb = process_thumbnail(&frame, 60, &style);
+        assert_eq!(*thumb.to_rgb8().get_pixel(0, 0), Rgb([0, 0, 0]));
+        let stripped = strip_border(&thumb, 5);
+        assert_eq!(stripped.dimensions(), (50, 25));
+        assert_eq!(*stripped.get_pixel(0, 0), Rgb([200, 0, 0]));
+    }
+
+    #[test]
+    fn layout_matches_compose_grid() {
+        let thumbs: Vec<Thumbnail> = (0..5)
+            .map(|i| make_thumbnail(10 + i, 8, i as f64))
+            .collect();
+        let (offsets, size) = layout(&thumbs, 2, &Style::default());
+        assert_eq!(offsets, vec![(0, 0), (10, 0), (0, 8), (12, 8), (0, 16)]);
+
+        let grid = compose_grid(&thumbs, 2, None, &Style::default()).expect("grid");
+        assert_eq!(size, grid.dimensions());
+
+        let spaced = Style {
+            gutter: 3,
+            ..Style::default()
+        };
+        let (offsets, size) = layout(&thumbs, 2, &spaced);
+        assert_eq!(offsets[3], (15, 11));
+        let grid = compose_grid(&thumbs, 2, None, &spaced).expect("grid");
+        assert_eq!(size, grid.dimensions());
+    }
+
+    #[test]
+    fn grid_origin_centres_below_header() {
+        let style = Style::default();
+        assert_eq!(grid_origin((100, 30), (60, 40), &style), (20, 30));
+        assert_eq!(grid_origin((50, 30), (60, 40), &style), (0, 30));
+        assert_eq!(grid_origin((0, 0), (60, 40), &style), (0, 0));
+
+        let style = Style { margin: 5, ..style };
+        assert_eq!(grid_origin((100, 30), (60, 40), &style), (25, 35));
+    }
+
     #[test]
     fn concat_horizontal_joins_images() {
         let images = vec![
             make_test_image(10, 20, Rgb([0, 0, 0])),
             make_test_image(15, 20, Rgb([128, 128, 128])),
         ];
-        let result = concat_horizontal(&images);
+        let result = concat_horizontal(&images, 0, Color::WHITE);
         assert_eq!(result.width(), 25);
         assert_eq!(result.height(), 20);
     }
 
     #[test]
     fn concat_vertical_joins_images() {
-        let a = RgbImage::from_pixel(10, 5, Rgb([0, 0, 0]));
-        let b = RgbImage::from_pixel(10, 8, Rgb([128, 128, 128]));
-        let result = concat_vertical(&[a, b]);
+        let a = RgbaImage::from_pixel(10, 5, Rgba([0, 0, 0, 255]));
+        let b = RgbaImage::from_pixel(10, 8, Rgba([128, 128, 128, 255]));
+        let result = concat_vertical(&[a, b], 0, Color::WHITE);
         assert_eq!(result.width(), 10);
         assert_eq!(result.height(), 13);
     }
 
     #[test]
     fn compose_grid_layout_3x2() {
-        let thumbs: Vec<DynamicImage> = (0..6)
-            .map(|_| make_test_image(10, 10, Rgb([50, 50, 50])))
-            .collect();
-        let result = compose_grid(&thumbs, 3);
+        let thumbs: Vec<Thumbnail> = (0..6).map(|i| make_thumbnail(10, 10, i as f64)).collect();
+        let result = compose_grid(&thumbs, 3, None, &Style::default()).expect("grid");
         assert_eq!(result.width(), 30);
         assert_eq!(result.height(), 20);
     }
 
     #[test]
     fn compose_grid_incomplete_last_row() {
-        let thumbs: Vec<DynamicImage> = (0..5)
-            .map(|_| make_test_image(10, 10, Rgb([50, 50, 50])))
-            .collect();
-        let result = compose_grid(&thumbs, 3);
+        let thumbs: Vec<Thumbnail> = (0..5).map(|i| make_thumbnail(10, 10, i as f64)).collect();
+        let result = compose_grid(&thumbs, 3, None, &Style::default()).expect("grid");
         assert_eq!(result.width(), 30);
         assert_eq!(result.height(), 20);
     }
 
+    #[test]
+    fn compose_grid_draws_timestamp_overlay() {
+        let thumbs = vec![make_thumbnail(160, 90, 75.0)];
+        let plain = compose_grid(&thumbs, 1, None, &Style::default()).expect("grid");
+        let labeled = compose_grid(
+            &thumbs,
+            1,
+            Some(&TimestampOverlay::default()),
+            &Style::default(),
+        )
+        .expect("grid");
+        assert_eq!(plain.dimensions(), labeled.dimensions());
+        // Top-left is untouched, bottom-right corner carries the label box
+        assert_eq!(labeled.get_pixel(0, 0), plain.get_pixel(0, 0));
+        assert_ne!(labeled.get_pixel(150, 80), plain.get_pixel(150, 80));
+    }
+
+    #[test]
+    fn overlay_text_formats_time_and_frame() {
+        let thumb = make_thumbnail(10, 10, 3725.0);
+        let mut overlay = TimestampOverlay::default();
+        assert_eq!(overlay.text(&thumb), "01:02:05");
+        overlay.frame_numbers = true;
+        assert_eq!(overlay.text(&thumb), "01:02:05 #93125");
+    }
+
     #[test]
     fn assemble_final_centers_header() {
-        let header = RgbImage::from_pixel(20, 5, Rgb([0, 0, 0]));
-        let grid = RgbImage::from_pixel(40, 10, Rgb([128, 128, 128]));
-        let result = assemble_final(&header, &grid);
+        let header = RgbaImage::from_pixel(20, 5, Rgba([0, 0, 0, 255]));
+        let grid = RgbaImage::from_pixel(40, 10, Rgba([128, 128, 128, 255]));
+        let result = assemble_final(Some(&header), &grid, &Style::default());
         assert_eq!(result.width(), 40);
         assert_eq!(result.height(), 15);
     }
+
+    #[test]
+    fn assemble_final_applies_margin_and_header_band() {
+        let header = RgbaImage::from_pixel(20, 5, Rgba([0, 0, 0, 255]));
+        let grid = RgbaImage::from_pixel(40, 10, Rgba([128, 128, 128, 255]));
+        let style = Style {
+            margin: 4,
+            background: Color::rgb(10, 10, 10),
+            header_background: Color::rgb(200, 0, 0),
+            ..Style::default()
+        };
+        let result = assemble_final(Some(&header), &grid, &style);
+        assert_eq!(result.dimensions(), (48, 23));
+        assert_eq!(*result.get_pixel(0, 0), Rgba([10, 10, 10, 255]));
+        assert_eq!(*result.get_pixel(0, 4), Rgba([200, 0, 0, 255]));
+        assert_eq!(*result.get_pixel(4, 9), Rgba([128, 128, 128, 255]));
+
+        let bare = assemble_final(None, &grid, &Style::default());
+        assert_eq!(bare, grid);
+    }
+
+    #[test]
+    fn transparent_style_leaves_gaps_clear() {
+        let thumbs: Vec<Thumbnail> = (0..2)
+            .map(|i| Thumbnail {
+                image: process_thumbnail(
+                    &make_test_image(40, 20, Rgb([50, 50, 50])),
+                    44,
+                    &Theme::Transparent.style(),
+                ),
+                time: i as f64,
+                frame: i,
+            })
+            .collect();
+        let style = Style {
+            gutter: 4,
+            margin: 3,
+            ..Theme::Transparent.style()
+        };
+        let grid =
+            compose_grid(&thumbs, 2, Some(&TimestampOverlay::default()), &style).expect("grid");
+        let result = assemble_final(None, &grid, &style);
+        assert_eq!(result.dimensions(), (98, 30));
+        // Margin, border and gutter are clear, the frames opaque
+        assert_eq!(result.get_pixel(0, 0).0[3], 0);
+        assert_eq!(result.get_pixel(4, 4).0[3], 0);
+        assert_eq!(result.get_pixel(49, 10).0[3], 0);
+        assert_eq!(*result.get_pixel(10, 6), Rgba([50, 50, 50, 255]));
+    }
 }
@@ -1,28 +1,41 @@
 use ab_glyph::{FontRef, PxScale};
-use image::{Rgb, RgbImage};
+use image::{Rgba, RgbaImage};
 use imageproc::drawing::{draw_text_mut, text_size};
 
 use crate::error::{Result, ThumbsdownError};
+use crate::style::Style;
+use crate::timecode;
 use crate::video::VideoInfo;
 
 const FONT_DATA: &[u8] = include_bytes!("../fonts/DejaVuSans.ttf");
 const FONT_SIZE: f32 = 18.0;
 const LINE_SPACING: i32 = 4;
 const PADDING: i32 = 8;
-const TEXT_COLOR: Rgb<u8> = Rgb([0, 0, 0]);
-const BG_COLOR: Rgb<u8> = Rgb([255, 255, 255]);
 
-pub fn render_header(info: &VideoInfo) -> Result<RgbImage> {
-    let font = FontRef::try_from_slice(FONT_DATA)
-        .map_err(|e| ThumbsdownError::FontError(e.to_string()))?;
+/// The embedded DejaVu Sans font used for all rendered text.
+pub fn font() -> Result<FontRef<'static>> {
+    FontRef::try_from_slice(FONT_DATA).map_err(|e| ThumbsdownError::FontError(e.to_string()))
+}
+
+/// Renders the header in the style's header colors, using `template` instead
+/// of the built-in layout when given and followed by a `Page X/Y` line when
+/// `page` is given as `(page, pages)`.
+pub fn render_header(
+    info: &VideoInfo,
+    template: Option<&str>,
+    page: Option<(usize, usize)>,
+    style: &Style,
+) -> Result<RgbaImage> {
+    let font = font()?;
     let scale = PxScale::from(FONT_SIZE);
 
-    let line1 = &info.filename;
-    let line2 = format!(
-        "vcodec: {}, fps: {:.2}, resolution: {}x{}",
-        info.codec, info.fps, info.width, info.height
-    );
-    let lines = [line1.as_str(), line2.as_str()];
+    let mut lines = match template {
+        Some(template) => expand_template(template, info)?,
+        None => header_lines(info),
+    };
+    if let Some((page, pages)) = page {
+        lines.push(format!("Page {page}/{pages}"));
+    }
 
     let measurements: Vec<(u32, u32)> = lines
         .iter()
@@ -31,25 +44,209 @@ pub fn render_header(info: &VideoInfo) -> Result<RgbImage> {
 
     let max_width = measurements.iter().map(|(w, _)| *w).max().unwrap_or(0);
     let total_text_height: u32 = measurements.iter().map(|(_, h)| *h).sum();
-    let total_spacing = LINE_SPACING * (lines.len() as i32 - 1);
+    let total_spacing = LINE_SPACING as u32 * lines.len().saturating_sub(1) as u32;
 
     let img_width = max_width + (PADDING as u32 * 2);
-    let img_height = total_text_height + total_spacing as u32 + (PADDING as u32 * 2);
+    let img_height = total_text_height + total_spacing + (PADDING as u32 * 2);
 
-    let mut image = RgbImage::from_pixel(img_width, img_height, BG_COLOR);
+    // Text goes on a clear layer of its own color, so its antialiased edges
+    // keep that color whatever the background's alpha
+    let text = style.header_text.rgba();
+    let [r, g, b, _] = text.0;
+    let mut layer = RgbaImage::from_pixel(img_width, img_height, Rgba([r, g, b, 0]));
 
     let mut y = PADDING;
     for (i, line) in lines.iter().enumerate() {
-        draw_text_mut(&mut image, TEXT_COLOR, PADDING, y, scale, &font, line);
+        draw_text_mut(&mut layer, text, PADDING, y, scale, &font, line);
         y += measurements[i].1 as i32 + LINE_SPACING;
     }
 
+    let mut image = RgbaImage::from_pixel(img_width, img_height, style.header_background.rgba());
+    image::imageops::overlay(&mut image, &layer, 0, 0);
     Ok(image)
 }
 
+/// Names accepted inside `{...}` in a header template.
+pub const PLACEHOLDERS: &[&str] = &[
+    "filename",
+    "duration",
+    "size",
+    "bitrate",
+    "container",
+    "codec",
+    "pix_fmt",
+    "fps",
+    "width",
+    "height",
+    "resolution",
+    "stored_resolution",
+    "rotation",
+    "acodec",
+    "channels",
+    "channel_layout",
+    "sample_rate",
+];
+
+const MISSING: &str = "n/a";
+
+/// Expands `{placeholder}`s in a header template into header lines. Lines are
+/// split on newlines or a literal `\n`; `{{` and `}}` produce literal braces.
+pub fn expand_template(template: &str, info: &VideoInfo) -> Result<Vec<String>> {
+    let template = template.replace("\\n", "\n");
+    let mut out = String::with_capacity(template.len());
+    let mut chars = template.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                out.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                out.push('}');
+            }
+            '{' => {
+                let mut name = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => name.push(c),
+                        None => return Err(ThumbsdownError::UnterminatedPlaceholder(name)),
+                    }
+                }
+                let value = placeholder(&name, info)
+                    .ok_or_else(|| ThumbsdownError::UnknownPlaceholder(name.clone()))?;
+                out.push_str(&value);
+            }
+            c => out.push(c),
+        }
+    }
+
+    let lines: Vec<String> = out.lines().map(str::to_string).collect();
+    if lines.is_empty() {
+        return Err(ThumbsdownError::EmptyHeaderTemplate);
+    }
+    Ok(lines)
+}
+
+fn placeholder(name: &str, info: &VideoInfo) -> Option<String> {
+    let or_missing = |value: Option<String>| value.unwrap_or_else(|| MISSING.to_string());
+    let audio = info.audio.as_ref();
+
+    let value = match name {
+        "filename" => info.filename.clone(),
+        "duration" => timecode::format_hms(info.duration),
+        "size" => or_missing(info.size.map(format_size)),
+        "bitrate" => or_missing(info.bitrate.map(format_bitrate)),
+        "container" => or_missing(info.container.clone()),
+        "codec" => info.codec.clone(),
+        "pix_fmt" => or_missing(info.pix_fmt.clone()),
+        "fps" => format!("{:.2}", info.fps),
+        "width" => info.display_size().0.to_string(),
+        "height" => info.display_size().1.to_string(),
+        "resolution" => format!("{}x{}", info.display_size().0, info.display_size().1),
+        "stored_resolution" => format!("{}x{}", info.width, info.height),
+        "rotation" => info.rotation.to_string(),
+        "acodec" => or_missing(audio.map(|a| a.codec.clone())),
+        "channels" => or_missing(audio.map(|a| a.channels.to_string())),
+        "channel_layout" => or_missing(audio.and_then(|a| a.channel_layout.clone())),
+        "sample_rate" => or_missing(audio.and_then(|a| a.sample_rate).map(|r| format!("{r} Hz"))),
+        _ => return None,
+    };
+    Some(value)
+}
+
+fn header_lines(info: &VideoInfo) -> Vec<String> {
+    let mut lines = vec![info.filename.clone()];
+
+    let mut file = vec![format!("duration: {}", timecode::format_hms(info.duration))];
+    if let Some(size) = info.size {
+        file.push(format!("size: {}", format_size(size)));
+    }
+    if let Some(bitrate) = info.bitrate {
+        file.push(format!("bitrate: {}", format_bitrate(bitrate)));
+    }
+    if let Some(ref container) = info.container {
+        file.push(format!("container: {container}"));
+    }
+    lines.push(file.join(", "));
+
+    let vcodec = match info.pix_fmt {
+        Some(ref pix_fmt) => format!("{} ({pix_fmt})", info.codec),
+        None => info.codec.clone(),
+    };
+    lines.push(format!(
+        "vcodec: {}, fps: {:.2}, resolution: {}",
+        vcodec,
+        info.fps,
+        resolution(info)
+    ));
+
+    if let Some(ref audio) = info.audio {
+        let mut parts = vec![format!("acodec: {}", audio.codec)];
+        match audio.channel_layout {
+            Some(ref layout) => parts.push(format!("{layout} ({} ch)", audio.channels)),
+            None => parts.push(format!("{} ch", audio.channels)),
+        }
+        if let Some(rate) = audio.sample_rate {
+            parts.push(format!("{rate} Hz"));
+        }
+        lines.push(parts.join(", "));
+    }
+
+    lines
+}
+
+/// The display resolution, followed by the stored one and what turns it
+/// into the display one when they differ.
+fn resolution(info: &VideoInfo) -> String {
+    let (width, height) = info.display_size();
+    let mut text = format!("{width}x{height}");
+    if (width, height) != (info.width, info.height) {
+        let mut changes = Vec::new();
+        if let Some((num, den)) = info.sample_aspect_ratio {
+            changes.push(format!("SAR {num}:{den}"));
+        }
+        if info.rotation != 0 {
+            changes.push(format!("rotated {}°", info.rotation));
+        }
+        text.push_str(&format!(
+            " (stored {}x{}, {})",
+            info.width,
+            info.height,
+            changes.join(", ")
+        ));
+    }
+    text
+}
+
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut value = bytes as f64;
+    let mut unit = "B";
+    for u in UNITS {
+        if value < 1024.0 {
+            break;
+        }
+        value /= 1024.0;
+        unit = u;
+    }
+    format!("{value:.2} {unit}")
+}
+
+fn format_bitrate(bits_per_sec: u64) -> String {
+    format!("{} kb/s", bits_per_sec / 1000)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::style::Theme;
+    use crate::video::AudioInfo;
 
     fn test_info() -> VideoInfo {
         VideoInfo {
@@ -59,28 +256,197 @@ mod tests {
             height: 1080,
             codec: "h264".to_string(),
             fps: 29.97,
+            ..Default::default()
+        }
+    }
+
+    fn full_info() -> VideoInfo {
+        VideoInfo {
+            pix_fmt: Some("yuv420p".to_string()),
+            size: Some(1_572_864),
+            bitrate: Some(4_500_000),
+            container: Some("QuickTime / MOV".to_string()),
+            audio: Some(AudioInfo {
+                codec: "aac".to_string(),
+                channels: 2,
+                channel_layout: Some("stereo".to_string()),
+                sample_rate: Some(48000),
+            }),
+            ..test_info()
+        }
+    }
+
+    #[test]
+    fn header_lines_minimal_info() {
+        let lines = header_lines(&test_info());
+        assert_eq!(
+            lines,
+            vec![
+                "test_video.mp4",
+                "duration: 00:02:00",
+                "vcodec: h264, fps: 29.97, resolution: 1920x1080",
+            ]
+        );
+    }
+
+    #[test]
+    fn header_lines_full_info() {
+        let lines = header_lines(&full_info());
+        assert_eq!(
+            lines[1],
+            "duration: 00:02:00, size: 1.50 MiB, bitrate: 4500 kb/s, container: QuickTime / MOV"
+        );
+        assert_eq!(
+            lines[2],
+            "vcodec: h264 (yuv420p), fps: 29.97, resolution: 1920x1080"
+        );
+        assert_eq!(lines[3], "acodec: aac, stereo (2 ch), 48000 Hz");
+    }
+
+    #[test]
+    fn header_lines_show_display_resolution() {
+        let anamorphic = VideoInfo {
+            width: 720,
+            height: 480,
+            sample_aspect_ratio: Some((32, 27)),
+            ..test_info()
+        };
+        assert_eq!(
+            header_lines(&anamorphic)[2],
+            "vcodec: h264, fps: 29.97, resolution: 853x480 (stored 720x480, SAR 32:27)"
+        );
+        let portrait = VideoInfo {
+            rotation: 90,
+            ..test_info()
+        };
+        assert_eq!(
+            header_lines(&portrait)[2],
+            "vcodec: h264, fps: 29.97, resolution: 1080x1920 (stored 1920x1080, rotated 90°)"
+        );
+        let lines = expand_template("{resolution} {stored_resolution} {rotation}", &portrait)
+            .expect("expand");
+        assert_eq!(lines, vec!["1080x1920 1920x1080 90"]);
+    }
+
+    #[test]
+    fn expand_template_substitutes_placeholders() {
+        let lines = expand_template("{filename}\n{codec} {resolution} @ {fps}", &full_info())
+            .expect("expand");
+        assert_eq!(lines, vec!["test_video.mp4", "h264 1920x1080 @ 29.97"]);
+    }
+
+    #[test]
+    fn expand_template_accepts_escaped_newline() {
+        let lines = expand_template("{duration}\\n{size}", &full_info()).expect("expand");
+        assert_eq!(lines, vec!["00:02:00", "1.50 MiB"]);
+    }
+
+    #[test]
+    fn expand_template_marks_missing_values() {
+        let lines = expand_template("{acodec} {bitrate}", &test_info()).expect("expand");
+        assert_eq!(lines, vec!["n/a n/a"]);
+    }
+
+    #[test]
+    fn expand_template_escapes_braces() {
+        let lines = expand_template("{{{width}}}", &test_info()).expect("expand");
+        assert_eq!(lines, vec!["{1920}"]);
+    }
+
+    #[test]
+    fn expand_template_rejects_unknown_placeholder() {
+        let err = expand_template("{filename} {nope}", &test_info()).unwrap_err();
+        assert!(matches!(err, ThumbsdownError::UnknownPlaceholder(ref name) if name == "nope"));
+    }
+
+    #[test]
+    fn expand_template_rejects_empty_template() {
+        let err = expand_template("", &test_info()).unwrap_err();
+        assert!(matches!(err, ThumbsdownError::EmptyHeaderTemplate));
+        assert!(render_header(&test_info(), Some(""), None, &Style::default()).is_err());
+        // A blank line is still a line
+        assert_eq!(
+            expand_template("\\n", &test_info()).expect("expand"),
+            vec![""]
+        );
+    }
+
+    #[test]
+    fn expand_template_rejects_unterminated_placeholder() {
+        let err = expand_template("{filename", &test_info()).unwrap_err();
+        assert!(matches!(err, ThumbsdownError::UnterminatedPlaceholder(_)));
+    }
+
+    #[test]
+    fn every_listed_placeholder_expands() {
+        for name in PLACEHOLDERS {
+            assert!(placeholder(name, &full_info()).is_some(), "{name}");
         }
     }
 
+    #[test]
+    fn render_header_uses_template() {
+        let default = render_header(&test_info(), None, None, &Style::default()).expect("render");
+        let single = render_header(&test_info(), Some("{filename}"), None, &Style::default())
+            .expect("render");
+        assert!(single.height() < default.height());
+    }
+
+    #[test]
+    fn format_size_picks_binary_unit() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(2048), "2.00 KiB");
+        assert_eq!(format_size(3 * 1024 * 1024 * 1024), "3.00 GiB");
+    }
+
+    #[test]
+    fn render_header_adds_page_line() {
+        let plain = render_header(&test_info(), None, None, &Style::default()).expect("render");
+        let paged =
+            render_header(&test_info(), None, Some((2, 3)), &Style::default()).expect("render");
+        assert!(paged.height() > plain.height());
+    }
+
     #[test]
     fn render_header_produces_image() {
-        let img = render_header(&test_info()).expect("render");
+        let img = render_header(&test_info(), None, None, &Style::default()).expect("render");
         assert!(img.width() > 0);
         assert!(img.height() > 0);
     }
 
     #[test]
     fn render_header_has_reasonable_height() {
-        let img = render_header(&test_info()).expect("render");
-        // Two lines of 18px text + spacing + padding should be > 40px
+        let img = render_header(&test_info(), None, None, &Style::default()).expect("render");
+        // Three lines of 18px text + spacing + padding should be > 40px
         assert!(img.height() > 40);
         assert!(img.height() < 200);
     }
 
     #[test]
     fn render_header_contains_non_white_pixels() {
-        let img = render_header(&test_info()).expect("render");
-        let has_text = img.pixels().any(|p| *p != Rgb([255, 255, 255]));
+        let img = render_header(&test_info(), None, None, &Style::default()).expect("render");
+        let has_text = img.pixels().any(|p| *p != Rgba([255, 255, 255, 255]));
         assert!(has_text, "header should contain drawn text");
     }
+
+    #[test]
+    fn render_header_uses_style_colors() {
+        let style = Theme::Dark.style();
+        let img = render_header(&test_info(), None, None, &style).expect("render");
+        assert_eq!(*img.get_pixel(0, 0), style.header_background.rgba());
+        assert!(img.pixels().any(|p| p.0[0] > 128), "light text is drawn");
+    }
+
+    #[test]
+    fn render_header_keeps_text_opaque_on_transparent_background() {
+        let style = Theme::Transparent.style();
+        let img = render_header(&test_info(), None, None, &style).expect("render");
+        assert_eq!(*img.get_pixel(0, 0), Rgba([0, 0, 0, 0]));
+        assert!(img.pixels().any(|p| p.0 == [0, 0, 0, 255]), "solid text");
+        // Antialiased edges fade out in alpha only
+        assert!(img
+            .pixels()
+            .filter(|p| p.0[3] > 0)
+            .all(|p| p.0[..3] == [0, 0, 0]));
+    }
 }
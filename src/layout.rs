@@ -0,0 +1,548 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::error::{Result, ThumbsdownError};
+use crate::style::Style;
+
+/// Number of grid columns: a fixed count, or chosen to suit the sheet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Columns {
+    Count(u32),
+    /// The count that gives the largest tiles within a sheet size with a
+    /// height, otherwise the one that brings the sheet closest to the target
+    /// aspect ratio
+    Auto,
+    /// As many as spread the thumbnails over this many rows
+    Rows(u32),
+}
+
+impl From<u32> for Columns {
+    fn from(count: u32) -> Self {
+        Columns::Count(count)
+    }
+}
+
+impl FromStr for Columns {
+    type Err = ThumbsdownError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if s.trim().eq_ignore_ascii_case("auto") {
+            return Ok(Columns::Auto);
+        }
+        s.trim()
+            .parse()
+            .map(Columns::Count)
+            .map_err(|_| ThumbsdownError::InvalidColumns(s.to_string()))
+    }
+}
+
+impl fmt::Display for Columns {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Columns::Count(count) => write!(f, "{count}"),
+            Columns::Auto => f.write_str("auto"),
+            Columns::Rows(rows) => write!(f, "{rows} rows"),
+        }
+    }
+}
+
+// A count is written as a number so config files keep `columns = 4`
+impl Serialize for Columns {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        match self {
+            Columns::Count(count) => serializer.serialize_u32(*count),
+            columns => serializer.collect_str(columns),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Columns {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Count(u32),
+            Name(String),
+        }
+        match Repr::deserialize(deserializer)? {
+            Repr::Count(count) => Ok(Columns::Count(count)),
+            Repr::Name(name) => name.parse().map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+/// Maximum size of the whole sheet, parsed from `WIDTHxHEIGHT` or a width
+/// alone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct SheetSize {
+    pub width: u32,
+    pub height: Option<u32>,
+}
+
+impl FromStr for SheetSize {
+    type Err = ThumbsdownError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || ThumbsdownError::InvalidSheetSize(s.to_string());
+        let side = |text: &str| match text.trim().parse() {
+            Ok(0) | Err(_) => Err(invalid()),
+            Ok(side) => Ok(side),
+        };
+        match s.split_once(['x', 'X']) {
+            Some((width, height)) => Ok(SheetSize {
+                width: side(width)?,
+                height: Some(side(height)?),
+            }),
+            None => Ok(SheetSize {
+                width: side(s)?,
+                height: None,
+            }),
+        }
+    }
+}
+
+impl fmt::Display for SheetSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.width)?;
+        if let Some(height) = self.height {
+            write!(f, "x{height}")?;
+        }
+        Ok(())
+    }
+}
+
+impl TryFrom<String> for SheetSize {
+    type Error = ThumbsdownError;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+impl From<SheetSize> for String {
+    fn from(size: SheetSize) -> Self {
+        size.to_string()
+    }
+}
+
+/// Target width:height of the sheet, parsed from `W:H` such as `16:9` or a
+/// single ratio such as `2.39`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Aspect {
+    pub width: f64,
+    pub height: f64,
+}
+
+impl Default for Aspect {
+    fn default() -> Self {
+        Aspect {
+            width: 16.0,
+            height: 9.0,
+        }
+    }
+}
+
+impl Aspect {
+    pub fn ratio(self) -> f64 {
+        self.width / self.height
+    }
+}
+
+impl FromStr for Aspect {
+    type Err = ThumbsdownError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || ThumbsdownError::InvalidAspect(s.to_string());
+        let side = |text: &str| match text.trim().parse::<f64>() {
+            Ok(side) if side.is_finite() && side > 0.0 => Ok(side),
+            _ => Err(invalid()),
+        };
+        match s.split_once([':', '/']) {
+            Some((width, height)) => Ok(Aspect {
+                width: side(width)?,
+                height: side(height)?,
+            }),
+            None => Ok(Aspect {
+                width: side(s)?,
+                height: 1.0,
+            }),
+        }
+    }
+}
+
+impl fmt::Display for Aspect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.height == 1.0 {
+            write!(f, "{}", self.width)
+        } else {
+            write!(f, "{}:{}", self.width, self.height)
+        }
+    }
+}
+
+impl TryFrom<String> for Aspect {
+    type Error = ThumbsdownError;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+impl From<Aspect> for String {
+    fn from(aspect: Aspect) -> Self {
+        aspect.to_string()
+    }
+}
+
+/// What `fit` lays out on the sheet besides the thumbnails' own size.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Contents {
+    /// Size of the captured frames
+    pub frame: (u32, u32),
+    /// Thumbnails on the fullest page
+    pub count: usize,
+    /// Rows allowed on a page, if limited
+    pub max_rows: Option<u32>,
+    /// Height of the header above the grid; 0 without one
+    pub header_height: u32,
+    /// Shape `Columns::Auto` aims for when there is no height to fill
+    pub aspect: Aspect,
+}
+
+/// Column count and thumbnail width chosen by `fit`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fit {
+    pub columns: u32,
+    /// Thumbnail width including its border, as `process_thumbnail` takes it
+    pub width: u32,
+}
+
+/// Picks the largest thumbnail width, and with `Columns::Auto` the column
+/// count, for which the grid and header fit `size` within the style's
+/// margin. Without a height to fill, `Columns::Auto` goes by the aspect
+/// ratio as `columns` does. The header is never scaled, so one wider than
+/// `size` still overflows it.
+pub fn fit(size: SheetSize, columns: Columns, contents: &Contents, style: &Style) -> Result<Fit> {
+    let too_small = || ThumbsdownError::SheetTooSmall(size.to_string());
+    let counts = match (columns, size.height) {
+        (Columns::Auto, Some(_)) => 1..=(contents.count.max(1) as u32),
+        (Columns::Auto, None) => {
+            let count = closest_columns(contents, style, |columns| {
+                tile_width(size, columns, contents, style)
+            })
+            .ok_or_else(too_small)?;
+            count..=count
+        }
+        (columns, _) => {
+            let count = fixed_columns(columns, contents);
+            count..=count
+        }
+    };
+    counts
+        .filter_map(|columns| {
+            let width = tile_width(size, columns, contents, style)?;
+            Some(Fit { columns, width })
+        })
+        // Ties go to the fewest columns, which leaves the fewest empty slots
+        .max_by_key(|fit| (fit.width, std::cmp::Reverse(fit.columns)))
+        .ok_or_else(too_small)
+}
+
+/// The column count for thumbnails `width` pixels wide on a sheet without
+/// a size.
+pub fn columns(columns: Columns, width: u32, contents: &Contents, style: &Style) -> u32 {
+    match columns {
+        Columns::Auto => closest_columns(contents, style, |_| Some(width)).unwrap_or(1),
+        columns => fixed_columns(columns, contents),
+    }
+}
+
+/// The count given by `Columns::Count` or `Columns::Rows`.
+fn fixed_columns(columns: Columns, contents: &Contents) -> u32 {
+    match columns {
+        Columns::Count(count) => count.max(1),
+        Columns::Rows(rows) => contents.count.max(1).div_ceil(rows.max(1) as usize) as u32,
+        // Without a size or target to go by, a single column
+        Columns::Auto => 1,
+    }
+}
+
+/// The column count whose sheet comes closest to the target aspect ratio,
+/// with `width` giving the thumbnail width for a count, if any fits.
+fn closest_columns(
+    contents: &Contents,
+    style: &Style,
+    width: impl Fn(u32) -> Option<u32>,
+) -> Option<u32> {
+    let target = contents.aspect.ratio().ln();
+    (1..=contents.count.max(1) as u32)
+        .filter_map(|columns| {
+            let (sheet_w, sheet_h) = sheet_size(columns, width(columns)?, contents, style);
+            // Compared on a log scale, so 2:1 and 1:2 are equally far from 1:1
+            let distance = ((sheet_w as f64 / sheet_h.max(1) as f64).ln() - target).abs();
+            Some((columns, distance))
+        })
+        // Ties go to the fewest columns, as `fit` does
+        .min_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)))
+        .map(|(columns, _)| columns)
+}
+
+/// Size of a page holding the fullest page of thumbnails in `columns`
+/// columns of `width` pixels, as `assemble_final` would make it.
+fn sheet_size(columns: u32, width: u32, contents: &Contents, style: &Style) -> (u32, u32) {
+    let (margin, gutter) = (style.margin, style.gutter);
+    let rows = rows(columns, contents);
+    let tile_height = tile_height(contents.frame, width, style);
+    (
+        columns * width + (columns - 1) * gutter + margin * 2,
+        rows * tile_height + (rows - 1) * gutter + contents.header_height + margin * 2,
+    )
+}
+
+/// Height of a thumbnail `width` pixels wide, border included, as
+/// `process_thumbnail` scales a frame of size `frame`.
+pub fn tile_height(frame: (u32, u32), width: u32, style: &Style) -> u32 {
+    let border = style.border_width;
+    let inner = width.saturating_sub(border * 2).max(1) as f64;
+    let height = (inner * frame.1 as f64 / frame.0.max(1) as f64).round() as u32;
+    height.max(1) + border * 2
+}
+
+/// Rows on the fullest page with `columns` columns.
+fn rows(columns: u32, contents: &Contents) -> u32 {
+    let rows = contents.count.max(1).div_ceil(columns as usize) as u32;
+    contents.max_rows.map_or(rows, |max| rows.min(max.max(1)))
+}
+
+/// Widest thumbnail for which `columns` columns fit the sheet size, or
+/// `None` when not even a border fits.
+fn tile_width(size: SheetSize, columns: u32, contents: &Contents, style: &Style) -> Option<u32> {
+    let (margin, gutter, border) = (style.margin, style.gutter, style.border_width);
+    let grid_width = size
+        .width
+        .checked_sub(margin * 2)?
+        .checked_sub((columns - 1) * gutter)?;
+    let mut width = grid_width / columns;
+
+    if let Some(height) = size.height {
+        let rows = rows(columns, contents);
+        let grid_height = height
+            .checked_sub(margin * 2 + contents.header_height)?
+            .checked_sub((rows - 1) * gutter)?;
+        let max_height = grid_height / rows;
+        // Start from the exact ratio and step down past rounding
+        let (frame_w, frame_h) = (contents.frame.0 as f64, contents.frame.1.max(1) as f64);
+        let inner = max_height.checked_sub(border * 2)? as f64;
+        let mut fitting = ((inner + 0.5) * frame_w / frame_h) as u32 + border * 2;
+        while fitting > border * 2 && tile_height(contents.frame, fitting, style) > max_height {
+            fitting -= 1;
+        }
+        width = width.min(fitting);
+    }
+
+    (width > border * 2).then_some(width)
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{DynamicImage, RgbImage};
+
+    use super::*;
+    use crate::grid;
+
+    fn contents(frame: (u32, u32), count: usize) -> Contents {
+        Contents {
+            frame,
+            count,
+            max_rows: None,
+            header_height: 0,
+            aspect: Aspect::default(),
+        }
+    }
+
+    #[test]
+    fn parses_sheet_sizes() {
+        let size: SheetSize = "1920x1080".parse().expect("parse");
+        assert_eq!((size.width, size.height), (1920, Some(1080)));
+        let size: SheetSize = "800".parse().expect("parse");
+        assert_eq!((size.width, size.height), (800, None));
+        assert_eq!(size.to_string(), "800");
+        assert_eq!(
+            "640X480".parse::<SheetSize>().expect("parse").to_string(),
+            "640x480"
+        );
+        for input in ["", "x1080", "1920x", "0x100", "axb", "1920x1080x2"] {
+            assert!(input.parse::<SheetSize>().is_err(), "{input}");
+        }
+    }
+
+    #[test]
+    fn columns_serialize_as_number_or_auto() {
+        assert_eq!(
+            serde_json::to_string(&Columns::Count(4)).expect("json"),
+            "4"
+        );
+        assert_eq!(
+            serde_json::to_string(&Columns::Auto).expect("json"),
+            "\"auto\""
+        );
+        let parsed: Columns = serde_json::from_str("\"auto\"").expect("json");
+        assert_eq!(parsed, Columns::Auto);
+        let parsed: Columns = serde_json::from_str("6").expect("json");
+        assert_eq!(parsed, Columns::Count(6));
+        assert!(serde_json::from_str::<Columns>("\"four\"").is_err());
+        assert_eq!(
+            serde_json::to_string(&Columns::Rows(3)).expect("json"),
+            "\"3 rows\""
+        );
+    }
+
+    #[test]
+    fn parses_aspect_ratios() {
+        assert_eq!("16:9".parse::<Aspect>().expect("parse"), Aspect::default());
+        assert_eq!("4/3".parse::<Aspect>().expect("parse").to_string(), "4:3");
+        let wide: Aspect = "2.39".parse().expect("parse");
+        assert_eq!(wide.ratio(), 2.39);
+        assert_eq!(wide.to_string(), "2.39");
+        for input in ["", "16:", ":9", "0:1", "-1", "inf", "wide"] {
+            assert!(input.parse::<Aspect>().is_err(), "{input}");
+        }
+    }
+
+    #[test]
+    fn tile_height_matches_process_thumbnail() {
+        let style = Style::default();
+        for (frame, width) in [((1920, 1080), 320), ((720, 1280), 101), ((853, 480), 77)] {
+            let image = DynamicImage::ImageRgb8(RgbImage::new(frame.0, frame.1));
+            let thumb = grid::process_thumbnail(&image, width, &style);
+            assert_eq!(
+                tile_height(frame, width, &style),
+                thumb.height(),
+                "{frame:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn width_only_divides_it_between_columns() {
+        let size = SheetSize {
+            width: 1000,
+            height: None,
+        };
+        let style = Style {
+            gutter: 10,
+            margin: 5,
+            ..Style::default()
+        };
+        let fit = fit(size, Columns::Count(4), &contents((1920, 1080), 20), &style).expect("fit");
+        // 1000 - 2 * 5 - 3 * 10 = 960 across 4 columns
+        assert_eq!(
+            fit,
+            Fit {
+                columns: 4,
+                width: 240
+            }
+        );
+    }
+
+    #[test]
+    fn height_limits_the_width() {
+        let size: SheetSize = "1920x400".parse().expect("parse");
+        let contents = Contents {
+            header_height: 40,
+            ..contents((1920, 1080), 20)
+        };
+        let style = Style::default();
+        let fit = fit(size, Columns::Count(5), &contents, &style).expect("fit");
+        // Four rows share 360 pixels
+        assert!(tile_height(contents.frame, fit.width, &style) <= 90);
+        assert!(tile_height(contents.frame, fit.width + 1, &style) > 90);
+    }
+
+    #[test]
+    fn auto_columns_fill_the_canvas() {
+        let style = Style::default();
+        let size: SheetSize = "1920x1080".parse().expect("parse");
+        let wide = fit(size, Columns::Auto, &contents((1920, 1080), 16), &style).expect("fit");
+        assert_eq!(wide.columns, 4);
+        // 480 would fill the width, but the borders push four rows past 1080
+        assert_eq!(wide.width, 477);
+        assert_eq!(4 * tile_height((1920, 1080), 477, &style), 1080);
+
+        // Portrait frames take more columns on the same canvas
+        let tall = fit(size, Columns::Auto, &contents((1080, 1920), 16), &style).expect("fit");
+        assert!(tall.columns > wide.columns);
+        let rows = 16u32.div_ceil(tall.columns);
+        let grid_height = rows * tile_height((1080, 1920), tall.width, &style);
+        assert!(tall.columns * tall.width <= 1920 && grid_height <= 1080);
+    }
+
+    #[test]
+    fn fit_reports_impossible_sizes() {
+        let style = Style::default();
+        let size: SheetSize = "100x20".parse().expect("parse");
+        let contents = Contents {
+            header_height: 30,
+            ..contents((1920, 1080), 4)
+        };
+        assert!(matches!(
+            fit(size, Columns::Count(2), &contents, &style),
+            Err(ThumbsdownError::SheetTooSmall(_))
+        ));
+    }
+
+    #[test]
+    fn auto_columns_approach_the_target_aspect() {
+        let style = Style::default();
+        let columns = |frame, aspect: &str| {
+            let contents = Contents {
+                aspect: aspect.parse().expect("aspect"),
+                ..contents(frame, 24)
+            };
+            columns(Columns::Auto, 320, &contents, &style)
+        };
+        // Portrait phone video gets more columns than the default 5,
+        // a 2.39:1 film fewer
+        assert!(columns((1080, 1920), "16:9") > 5);
+        assert!(columns((1920, 804), "16:9") < columns((1920, 1080), "16:9"));
+        assert!(columns((1920, 1080), "1:1") < columns((1920, 1080), "16:9"));
+        // 16:9 tiles make a 16:9 sheet as a square grid, here 5x5 less one
+        assert_eq!(columns((1920, 1080), "16:9"), 5);
+    }
+
+    #[test]
+    fn rows_spread_thumbnails_over_columns() {
+        let contents = contents((1920, 1080), 20);
+        let style = Style::default();
+        assert_eq!(columns(Columns::Rows(3), 320, &contents, &style), 7);
+        assert_eq!(columns(Columns::Count(4), 320, &contents, &style), 4);
+
+        let size: SheetSize = "1400".parse().expect("parse");
+        let fit = fit(size, Columns::Rows(4), &contents, &style).expect("fit");
+        assert_eq!(
+            fit,
+            Fit {
+                columns: 5,
+                width: 280
+            }
+        );
+    }
+
+    #[test]
+    fn auto_columns_with_width_only_use_the_aspect() {
+        let style = Style::default();
+        let size: SheetSize = "1920".parse().expect("parse");
+        let contents = contents((1920, 1080), 24);
+        let fitted = fit(size, Columns::Auto, &contents, &style).expect("fit");
+        // Widths scale with the count, so the shape matches a fixed width
+        assert_eq!(
+            fitted.columns,
+            columns(Columns::Auto, 320, &contents, &style)
+        );
+        assert_eq!(fitted.width, 1920 / fitted.columns);
+    }
+}
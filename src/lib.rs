@@ -0,0 +1,22 @@
+pub mod animation;
+pub mod batch;
+pub mod bif;
+pub mod encode;
+pub mod error;
+pub mod fingerprint;
+pub mod grid;
+pub mod header;
+pub mod layout;
+pub mod manifest;
+pub mod pdf;
+pub mod quality;
+pub mod select;
+pub mod sheet;
+pub mod style;
+pub mod timecode;
+pub mod video;
+pub mod vtt;
+
+pub use error::{Result, ThumbsdownError};
+pub use sheet::{ContactSheet, Sheet};
+pub use video::{AudioInfo, VideoInfo};
@@ -1,104 +1,230 @@
 mod cli;
-mod error;
-mod grid;
-mod header;
-mod video;
+mod config;
 
+use std::path::{Path, PathBuf};
 use std::process;
 
-use clap::Parser;
 use indicatif::{ProgressBar, ProgressStyle};
-
-use crate::error::Result;
+use thumbsdown::fingerprint::{self, Fingerprint};
+use thumbsdown::{animation, batch, bif, video, Result, ThumbsdownError};
+
+/// What happened to a single input.
+enum Outcome {
+    Generated,
+    /// `--update` found the existing output up to date
+    Skipped,
+}
 
 fn main() {
-    if let Err(e) = run() {
-        eprintln!("Error: {e}");
-        process::exit(1);
+    match run() {
+        Ok(true) => {}
+        Ok(false) => process::exit(1),
+        Err(e) => {
+            eprintln!("Error: {e}");
+            process::exit(1);
+        }
     }
 }
 
-fn run() -> Result<()> {
-    let args = cli::Args::parse();
+/// Returns whether every input was processed successfully.
+fn run() -> Result<bool> {
+    let args = config::parse_args()?;
+    if args.print_config {
+        print!("{}", config::to_toml(&args)?);
+        return Ok(true);
+    }
     cli::validate(&args)?;
-    video::check_dependencies()?;
+    if args.temp.is_some() {
+        eprintln!(
+            "Warning: --temp is deprecated and has no effect; frames are streamed from ffmpeg"
+        );
+    }
 
-    if args.output.exists() && args.force {
-        if args.verbose {
-            eprintln!("Output file already exists: deleting");
-        }
-        std::fs::remove_file(&args.output)?;
+    let inputs = batch::collect_inputs(&args.videos, args.recursive, &args.ext)?;
+    if inputs.is_empty() {
+        return Err(ThumbsdownError::NoInputs);
+    }
+
+    // A single input keeps the plain error reporting of a one-off run
+    if let [input] = inputs.as_slice() {
+        let output = batch::output_path(&args.output, input)?;
+        cli::validate_job(&args, input, &output)?;
+        video::check_dependencies()?;
+        process_video(&args, input, &output, false)?;
+        return Ok(true);
     }
 
-    let temp_dir = match &args.temp {
-        Some(dir) => tempfile::Builder::new()
-            .prefix("thumbsdown_")
-            .tempdir_in(dir)?,
-        None => tempfile::Builder::new().prefix("thumbsdown_").tempdir()?,
+    if !args.no_grid && !batch::is_template(&args.output) {
+        return Err(ThumbsdownError::OutputNotTemplate);
+    }
+    let not_template = |path: &Option<PathBuf>| {
+        path.as_deref()
+            .is_some_and(|path| !batch::is_template(path))
     };
+    if not_template(&args.manifest) {
+        return Err(ThumbsdownError::ManifestNotTemplate);
+    }
+    if not_template(&args.bif) {
+        return Err(ThumbsdownError::BifNotTemplate);
+    }
+    if not_template(&args.animate) {
+        return Err(ThumbsdownError::AnimateNotTemplate);
+    }
+    video::check_dependencies()?;
 
-    if args.verbose {
-        eprintln!("Temp directory: {}", temp_dir.path().display());
+    let mut skipped = 0;
+    let mut failures: Vec<(PathBuf, ThumbsdownError)> = Vec::new();
+    for input in &inputs {
+        let result = batch::output_path(&args.output, input).and_then(|output| {
+            cli::validate_job(&args, input, &output)?;
+            process_video(&args, input, &output, true)
+        });
+        match result {
+            Ok(Outcome::Generated) => {}
+            Ok(Outcome::Skipped) => skipped += 1,
+            Err(e) => {
+                eprintln!("Error: {}: {e}", input.display());
+                failures.push((input.clone(), e));
+            }
+        }
     }
 
-    let info = video::probe(&args.video)?;
-    if args.verbose {
+    let succeeded = inputs.len() - skipped - failures.len();
+    if args.update {
+        eprintln!(
+            "Processed {} files: {succeeded} succeeded, {skipped} up to date, {} failed",
+            inputs.len(),
+            failures.len()
+        );
+    } else {
         eprintln!(
-            "Video: {} ({}x{}, {}, {:.2} fps, {:.1}s)",
-            info.filename, info.width, info.height, info.codec, info.fps, info.duration
+            "Processed {} files: {succeeded} succeeded, {} failed",
+            inputs.len(),
+            failures.len()
         );
     }
+    for (input, e) in &failures {
+        eprintln!("  {}: {e}", input.display());
+    }
 
-    let step = (info.duration - args.start as f64) / args.thumbs as f64;
+    Ok(failures.is_empty())
+}
 
-    let pb = ProgressBar::new(args.thumbs as u64);
-    if let Ok(style) = ProgressStyle::default_bar().template("{bar:40} {pos}/{len} frames") {
-        pb.set_style(style.progress_chars("=> "));
+fn process_video(args: &cli::Args, input: &Path, output: &Path, batch: bool) -> Result<Outcome> {
+    let mut sheet = args.contact_sheet(input, output);
+
+    let mut fingerprint = None;
+    if args.update {
+        let current = Fingerprint::compute(input, &args.fingerprint_params(&sheet))?;
+        let outputs = args.outputs(input, output)?;
+        if fingerprint::is_up_to_date(input, &outputs, &current) {
+            eprintln!("Up to date: {}", outputs[0].display());
+            return Ok(Outcome::Skipped);
+        }
+        // A run that fails halfway must not leave the old fingerprint behind
+        let sidecar = fingerprint::sidecar_path(&outputs[0]);
+        if sidecar.exists() {
+            std::fs::remove_file(sidecar)?;
+        }
+        fingerprint = Some((outputs[0].clone(), current));
     }
 
-    let mut thumbnails = Vec::with_capacity(args.thumbs as usize);
-    for i in 0..args.thumbs {
-        let time = args.start as f64 + (i as f64 * step);
-        if time > info.duration {
-            break;
+    if !args.no_grid && (args.force || args.update) {
+        // Pages of an earlier, longer sheet would otherwise be left behind
+        let existing = batch::existing_pages(output);
+        if args.verbose && !existing.is_empty() {
+            eprintln!("Output file already exists: deleting");
+        }
+        for path in existing {
+            std::fs::remove_file(path)?;
         }
+    }
 
-        let frame_path = temp_dir.path().join(format!("frame-{i:08}.png"));
-        video::capture_frame(&args.video, time, &frame_path)?;
+    if batch {
+        if let Some(ref keep_dir) = args.keep_frames {
+            // Frame names would collide between inputs, so each gets its own directory
+            let stem = input.file_stem().unwrap_or(input.as_os_str());
+            let dir = keep_dir.join(stem);
+            std::fs::create_dir_all(&dir)?;
+            sheet = sheet.keep_frames(dir);
+        }
+    }
 
-        let thumb = grid::process_thumbnail(&frame_path, args.width, 10)?;
-        thumbnails.push(thumb);
+    let info = sheet.probe()?;
+
+    // With --no-grid and only --bif there is nothing to capture thumbnails for
+    if !args.no_grid || args.keep_frames.is_some() || args.animate.is_some() {
+        let timestamps = sheet.timestamps(&info)?;
+        sheet = sheet.fit(&info, timestamps.len())?;
+        let pb = progress_bar(timestamps.len(), batch.then_some(&info.filename));
+        let thumbnails = sheet.capture(&info, &timestamps, || pb.inc(1))?;
+        pb.finish_and_clear();
+
+        if !args.no_grid {
+            let pages = sheet.render_pages(&info, &thumbnails)?;
+            sheet.save_pages(&pages)?;
+            sheet.write_vtt(&info, &thumbnails)?;
+
+            if let Some(ref template) = args.manifest {
+                let path = batch::output_path(template, input)?;
+                sheet.manifest(&info, &thumbnails, &pages)?.write(&path)?;
+                if args.verbose {
+                    eprintln!("Saved manifest to {}", path.display());
+                }
+            }
+        }
 
-        if let Some(ref keep_dir) = args.keep_frames {
-            let dest = keep_dir.join(format!("frame-{i:04}.png"));
-            std::fs::copy(&frame_path, &dest)?;
+        if let Some(ref template) = args.animate {
+            let path = batch::output_path(template, input)?;
+            let options = args.animation_options();
+            let frames = sheet.animation_frames(&info, &thumbnails, &options)?;
+            animation::save(&frames, &path, &options)?;
             if args.verbose {
-                eprintln!("Saved frame to {}", dest.display());
+                eprintln!("Saved animation to {}", path.display());
             }
         }
+    }
 
+    if let Some(ref template) = args.bif {
+        let path = batch::output_path(template, input)?;
+        let timestamps = bif::timestamps(&info, args.bif_interval)?;
         if args.verbose {
-            eprintln!("Captured frame at {time:.1}s -> {}", frame_path.display());
+            eprintln!("Capturing {} BIF frames", timestamps.len());
         }
-        pb.inc(1);
-    }
-    pb.finish_and_clear();
-
-    if !args.no_grid {
-        let grid_image = grid::compose_grid(&thumbnails, args.columns);
-        let header_image = header::render_header(&info)?;
-        let final_image = grid::assemble_final(&header_image, &grid_image);
-
-        final_image.save(&args.output)?;
-
+        let pb = progress_bar(timestamps.len(), batch.then_some(&info.filename));
+        let frames = bif::capture(
+            input,
+            &info,
+            args.bif_interval,
+            args.bif_width,
+            args.bif_quality,
+            || pb.inc(1),
+        )?;
+        pb.finish_and_clear();
+        bif::save(&path, args.bif_interval, &frames)?;
         if args.verbose {
-            eprintln!("Saved to {}", args.output.display());
+            eprintln!("Saved BIF to {}", path.display());
         }
     }
 
+    if let Some((path, fingerprint)) = fingerprint {
+        fingerprint.write(&path)?;
+    }
+
     if args.verbose {
         eprintln!("DONE.");
     }
 
-    Ok(())
+    Ok(Outcome::Generated)
+}
+
+fn progress_bar(len: usize, name: Option<&String>) -> ProgressBar {
+    let pb = ProgressBar::new(len as u64);
+    if let Ok(style) = ProgressStyle::default_bar().template("{bar:40} {pos}/{len} frames {msg}") {
+        pb.set_style(style.progress_chars("=> "));
+    }
+    if let Some(name) = name {
+        pb.set_message(name.clone());
+    }
+    pb
 }
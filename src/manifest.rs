@@ -0,0 +1,48 @@
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::error::Result;
+use crate::sheet::ContactSheet;
+use crate::video::VideoInfo;
+
+/// Everything known about a rendered sheet, so downstream tools need not
+/// probe the video again. Built by `ContactSheet::manifest`.
+#[derive(Debug, Clone, Serialize)]
+pub struct Manifest {
+    pub video: VideoInfo,
+    /// The parameters the sheet was generated with
+    pub params: ContactSheet,
+    pub pages: Vec<Page>,
+    pub tiles: Vec<Tile>,
+}
+
+/// A saved page. The pages of a PDF share its file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Page {
+    pub file: PathBuf,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// A thumbnail and its rectangle in pixels on its page.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Tile {
+    /// Index into `Manifest::pages`
+    pub page: usize,
+    /// Capture time in seconds
+    pub time: f64,
+    /// Frame number at the capture time
+    pub frame: u64,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Manifest {
+    pub fn write(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, serde_json::to_vec_pretty(self)?)?;
+        Ok(())
+    }
+}
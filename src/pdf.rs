@@ -0,0 +1,163 @@
+use std::io::{self, Write};
+
+/// A JPEG-encoded page image.
+#[derive(Debug, Clone)]
+pub struct JpegPage {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>,
+}
+
+/// Writes a minimal PDF with one page per image, each page sized to its
+/// image at 72 dpi so a pixel maps to a point.
+pub fn write<W: Write>(writer: W, pages: &[JpegPage]) -> io::Result<()> {
+    let mut out = Counter::new(writer);
+    // Object 1 is the catalog, 2 the page tree, then three objects per page
+    let page_id = |i: usize| 3 + i * 3;
+    let mut offsets: Vec<u64> = Vec::new();
+
+    out.write_all(b"%PDF-1.4\n%\xE2\xE3\xCF\xD3\n")?;
+
+    offsets.push(out.count);
+    out.write_all(b"1 0 obj\n<< /Type /Catalog /Pages 2 0 R >>\nendobj\n")?;
+
+    offsets.push(out.count);
+    let kids: Vec<String> = (0..pages.len())
+        .map(|i| format!("{} 0 R", page_id(i)))
+        .collect();
+    write!(
+        out,
+        "2 0 obj\n<< /Type /Pages /Kids [{}] /Count {} >>\nendobj\n",
+        kids.join(" "),
+        pages.len()
+    )?;
+
+    for (i, page) in pages.iter().enumerate() {
+        let id = page_id(i);
+        let (w, h) = (page.width, page.height);
+
+        offsets.push(out.count);
+        write!(
+            out,
+            "{id} 0 obj\n<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {w} {h}] \
+             /Resources << /XObject << /Im0 {} 0 R >> >> /Contents {} 0 R >>\nendobj\n",
+            id + 1,
+            id + 2
+        )?;
+
+        offsets.push(out.count);
+        write!(
+            out,
+            "{} 0 obj\n<< /Type /XObject /Subtype /Image /Width {w} /Height {h} \
+             /ColorSpace /DeviceRGB /BitsPerComponent 8 /Filter /DCTDecode /Length {} >>\nstream\n",
+            id + 1,
+            page.data.len()
+        )?;
+        out.write_all(&page.data)?;
+        out.write_all(b"\nendstream\nendobj\n")?;
+
+        let content = format!("q {w} 0 0 {h} 0 0 cm /Im0 Do Q");
+        offsets.push(out.count);
+        write!(
+            out,
+            "{} 0 obj\n<< /Length {} >>\nstream\n{content}\nendstream\nendobj\n",
+            id + 2,
+            content.len()
+        )?;
+    }
+
+    let xref = out.count;
+    write!(out, "xref\n0 {}\n0000000000 65535 f \n", offsets.len() + 1)?;
+    for offset in &offsets {
+        writeln!(out, "{offset:010} 00000 n ")?;
+    }
+    write!(
+        out,
+        "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{xref}\n%%EOF\n",
+        offsets.len() + 1
+    )?;
+    out.flush()
+}
+
+/// Tracks the byte offset needed for the cross-reference table.
+struct Counter<W> {
+    inner: W,
+    count: u64,
+}
+
+impl<W: Write> Counter<W> {
+    fn new(inner: W) -> Self {
+        Self { inner, count: 0 }
+    }
+}
+
+impl<W: Write> Write for Counter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.count += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn page(width: u32, height: u32) -> JpegPage {
+        JpegPage {
+            width,
+            height,
+            data: vec![0xFF, 0xD8, 0xFF, 0xD9],
+        }
+    }
+
+    fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+        haystack.windows(needle.len()).position(|w| w == needle)
+    }
+
+    #[test]
+    fn writes_one_page_per_image() {
+        let mut pdf = Vec::new();
+        write(&mut pdf, &[page(640, 480), page(320, 200)]).expect("write");
+
+        assert!(pdf.starts_with(b"%PDF-1.4"));
+        assert!(pdf.ends_with(b"%%EOF\n"));
+        assert!(find(&pdf, b"/Count 2").is_some());
+        assert!(find(&pdf, b"/MediaBox [0 0 320 200]").is_some());
+    }
+
+    #[test]
+    fn xref_offsets_point_at_objects() {
+        let mut pdf = Vec::new();
+        write(&mut pdf, &[page(10, 10), page(20, 20)]).expect("write");
+
+        let xref = find(&pdf, b"xref\n").expect("xref");
+        let table = std::str::from_utf8(&pdf[xref..]).expect("utf8");
+        let entries: Vec<usize> = table
+            .lines()
+            .skip(3)
+            .take_while(|line| line.ends_with(" n "))
+            .map(|line| line[..10].parse().expect("offset"))
+            .collect();
+        assert_eq!(entries.len(), 8);
+        for (i, offset) in entries.iter().enumerate() {
+            let header = format!("{} 0 obj", i + 1);
+            assert!(pdf[*offset..].starts_with(header.as_bytes()), "{header}");
+        }
+
+        let startxref = find(&pdf, b"startxref\n").expect("startxref") + 10;
+        let end = pdf[startxref..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .expect("eol");
+        let value: usize = std::str::from_utf8(&pdf[startxref..startxref + end])
+            .expect("utf8")
+            .parse()
+            .expect("number");
+        assert_eq!(value, xref);
+    }
+}
@@ -0,0 +1,206 @@
+use std::fmt;
+
+use image::{imageops, RgbImage};
+use serde::Serialize;
+
+pub const DEFAULT_MIN_BRIGHTNESS: f64 = 16.0;
+pub const DEFAULT_MIN_CONTRAST: f64 = 8.0;
+pub const DEFAULT_MIN_SHARPNESS: f64 = 15.0;
+pub const DEFAULT_RETRIES: u32 = 4;
+
+// Frames are measured on a small copy so thresholds don't depend on resolution
+const ANALYSIS_WIDTH: u32 = 256;
+
+/// Luma statistics of a captured frame.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameStats {
+    /// Mean luma, 0-255
+    pub brightness: f64,
+    /// Standard deviation of luma
+    pub contrast: f64,
+    /// Variance of the Laplacian of luma
+    pub sharpness: f64,
+}
+
+/// Why a frame was rejected.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Rejection {
+    Dark(f64),
+    Flat(f64),
+    Blurry(f64),
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rejection::Dark(v) => write!(f, "too dark (brightness {v:.1})"),
+            Rejection::Flat(v) => write!(f, "blank (contrast {v:.1})"),
+            Rejection::Blurry(v) => write!(f, "blurry (sharpness {v:.1})"),
+        }
+    }
+}
+
+/// Thresholds below which a frame is rejected and re-captured.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct FrameFilter {
+    pub min_brightness: f64,
+    pub min_contrast: f64,
+    pub min_sharpness: f64,
+    /// Re-captures attempted within a slot before settling for the best frame
+    pub retries: u32,
+}
+
+impl Default for FrameFilter {
+    fn default() -> Self {
+        Self {
+            min_brightness: DEFAULT_MIN_BRIGHTNESS,
+            min_contrast: DEFAULT_MIN_CONTRAST,
+            min_sharpness: DEFAULT_MIN_SHARPNESS,
+            retries: DEFAULT_RETRIES,
+        }
+    }
+}
+
+impl FrameFilter {
+    pub fn check(&self, stats: &FrameStats) -> Option<Rejection> {
+        if stats.brightness < self.min_brightness {
+            Some(Rejection::Dark(stats.brightness))
+        } else if stats.contrast < self.min_contrast {
+            Some(Rejection::Flat(stats.contrast))
+        } else if stats.sharpness < self.min_sharpness {
+            Some(Rejection::Blurry(stats.sharpness))
+        } else {
+            None
+        }
+    }
+}
+
+pub fn measure(frame: &RgbImage) -> FrameStats {
+    let small;
+    let frame = if frame.width() > ANALYSIS_WIDTH {
+        let height = (frame.height() as u64 * ANALYSIS_WIDTH as u64 / frame.width() as u64).max(1);
+        small = imageops::thumbnail(frame, ANALYSIS_WIDTH, height as u32);
+        &small
+    } else {
+        frame
+    };
+
+    let (w, h) = (frame.width() as usize, frame.height() as usize);
+    let luma: Vec<f64> = frame
+        .pixels()
+        .map(|p| 0.299 * p[0] as f64 + 0.587 * p[1] as f64 + 0.114 * p[2] as f64)
+        .collect();
+
+    let (brightness, contrast) = mean_and_std_dev(&luma);
+
+    let mut laplacian = Vec::with_capacity(w.saturating_sub(2) * h.saturating_sub(2));
+    for y in 1..h.saturating_sub(1) {
+        for x in 1..w.saturating_sub(1) {
+            let i = y * w + x;
+            laplacian.push(luma[i - 1] + luma[i + 1] + luma[i - w] + luma[i + w] - 4.0 * luma[i]);
+        }
+    }
+    let (_, sharpness_std_dev) = mean_and_std_dev(&laplacian);
+
+    FrameStats {
+        brightness,
+        contrast,
+        sharpness: sharpness_std_dev * sharpness_std_dev,
+    }
+}
+
+/// Alternative capture times for a rejected frame at `time`, alternating
+/// forward and backward in growing steps while staying inside `[lower, upper]`.
+pub fn nudges(time: f64, lower: f64, upper: f64, retries: u32) -> Vec<f64> {
+    let steps = retries.div_ceil(2) + 1;
+    let forward = (upper - time).max(0.0) / steps as f64;
+    let backward = (time - lower).max(0.0) / steps as f64;
+
+    (1..=retries)
+        .map(|k| {
+            let n = k.div_ceil(2) as f64;
+            if k % 2 == 1 {
+                time + forward * n
+            } else {
+                time - backward * n
+            }
+        })
+        .filter(|&t| t != time)
+        .collect()
+}
+
+fn mean_and_std_dev(values: &[f64]) -> (f64, f64) {
+    if values.is_empty() {
+        return (0.0, 0.0);
+    }
+    let n = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / n;
+    let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n;
+    (mean, variance.sqrt())
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgb;
+
+    use super::*;
+
+    fn checkerboard(w: u32, h: u32) -> RgbImage {
+        RgbImage::from_fn(w, h, |x, y| {
+            if (x / 2 + y / 2) % 2 == 0 {
+                Rgb([230, 230, 230])
+            } else {
+                Rgb([30, 30, 30])
+            }
+        })
+    }
+
+    #[test]
+    fn black_frame_is_dark() {
+        let stats = measure(&RgbImage::from_pixel(64, 36, Rgb([0, 0, 0])));
+        assert!(matches!(
+            FrameFilter::default().check(&stats),
+            Some(Rejection::Dark(_))
+        ));
+    }
+
+    #[test]
+    fn uniform_frame_is_flat() {
+        let stats = measure(&RgbImage::from_pixel(64, 36, Rgb([128, 128, 128])));
+        assert!(matches!(
+            FrameFilter::default().check(&stats),
+            Some(Rejection::Flat(_))
+        ));
+    }
+
+    #[test]
+    fn smooth_gradient_is_blurry() {
+        let img = RgbImage::from_fn(256, 64, |x, _| Rgb([x as u8, x as u8, x as u8]));
+        assert!(matches!(
+            FrameFilter::default().check(&measure(&img)),
+            Some(Rejection::Blurry(_))
+        ));
+    }
+
+    #[test]
+    fn detailed_frame_passes() {
+        let stats = measure(&checkerboard(64, 36));
+        assert_eq!(FrameFilter::default().check(&stats), None);
+    }
+
+    #[test]
+    fn large_frames_are_downscaled_for_analysis() {
+        let stats = measure(&RgbImage::from_pixel(1920, 1080, Rgb([200, 200, 200])));
+        assert!((stats.brightness - 200.0).abs() < 1.0);
+    }
+
+    #[test]
+    fn nudges_alternate_within_slot() {
+        assert_eq!(nudges(10.0, 4.0, 22.0, 4), vec![14.0, 8.0, 18.0, 6.0]);
+    }
+
+    #[test]
+    fn nudges_skip_exhausted_direction() {
+        assert_eq!(nudges(0.0, 0.0, 6.0, 2), vec![3.0]);
+    }
+}
@@ -0,0 +1,150 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+/// How capture timestamps are chosen.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Selection {
+    /// Evenly spaced between the start time and the end of the video
+    #[default]
+    Uniform,
+    /// The most distinct shots according to ffmpeg's scene-change score
+    Scenes,
+}
+
+/// `count` evenly spaced timestamps from `start` up to `duration`.
+pub fn uniform(start: f64, duration: f64, count: u32) -> Vec<f64> {
+    let step = (duration - start) / count as f64;
+    (0..count)
+        .map(|i| start + (i as f64 * step))
+        .take_while(|&time| time <= duration)
+        .collect()
+}
+
+/// Timestamps `interval` seconds apart from `start`, stopping before `end`.
+/// Empty unless `interval` is positive and finite.
+pub fn every(start: f64, end: f64, interval: f64) -> Vec<f64> {
+    if !(interval.is_finite() && interval > 0.0) {
+        return Vec::new();
+    }
+    (0..)
+        .map(|i| start + i as f64 * interval)
+        .take_while(|&time| time < end)
+        .collect()
+}
+
+/// Picks up to `count` scene cuts from `(time, score)` pairs, preferring the
+/// highest scores while keeping picks spread out. Slots that no cut can fill
+/// fall back to uniform timestamps. The result is sorted by time.
+pub fn pick_scenes(
+    scores: &[(f64, f64)],
+    threshold: f64,
+    start: f64,
+    duration: f64,
+    count: u32,
+) -> Vec<f64> {
+    let count = count as usize;
+    // Don't let one busy sequence claim several slots
+    let min_gap = (duration - start) / (count.max(1) * 4) as f64;
+
+    let mut cuts: Vec<(f64, f64)> = scores
+        .iter()
+        .copied()
+        .filter(|&(time, score)| score >= threshold && time >= start && time <= duration)
+        .collect();
+    cuts.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    let mut picks: Vec<f64> = Vec::with_capacity(count);
+    let far_enough = |picks: &[f64], time: f64| picks.iter().all(|p| (p - time).abs() >= min_gap);
+
+    for (time, _) in cuts {
+        if picks.len() == count {
+            break;
+        }
+        if far_enough(&picks, time) {
+            picks.push(time);
+        }
+    }
+
+    if picks.len() < count {
+        let fallback = uniform(start, duration, count as u32);
+        for &time in &fallback {
+            if picks.len() == count {
+                break;
+            }
+            if far_enough(&picks, time) {
+                picks.push(time);
+            }
+        }
+        for &time in &fallback {
+            if picks.len() == count {
+                break;
+            }
+            if !picks.contains(&time) {
+                picks.push(time);
+            }
+        }
+    }
+
+    picks.sort_by(f64::total_cmp);
+    picks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uniform_is_evenly_spaced() {
+        assert_eq!(uniform(0.0, 100.0, 4), vec![0.0, 25.0, 50.0, 75.0]);
+    }
+
+    #[test]
+    fn uniform_empty_when_start_past_end() {
+        assert!(uniform(50.0, 10.0, 3).is_empty());
+    }
+
+    #[test]
+    fn every_uses_fixed_cadence() {
+        assert_eq!(every(5.0, 100.0, 30.0), vec![5.0, 35.0, 65.0, 95.0]);
+        assert_eq!(every(0.0, 60.0, 30.0), vec![0.0, 30.0]);
+    }
+
+    #[test]
+    fn every_empty_for_invalid_interval() {
+        assert!(every(0.0, 60.0, 0.0).is_empty());
+        assert!(every(0.0, 60.0, -1.0).is_empty());
+        assert!(every(0.0, 60.0, f64::NAN).is_empty());
+    }
+
+    #[test]
+    fn pick_scenes_prefers_highest_scores() {
+        let scores = [(10.0, 0.9), (20.0, 0.2), (50.0, 0.8), (80.0, 0.95)];
+        assert_eq!(
+            pick_scenes(&scores, 0.3, 0.0, 100.0, 3),
+            vec![10.0, 50.0, 80.0]
+        );
+    }
+
+    #[test]
+    fn pick_scenes_skips_cuts_too_close_together() {
+        let scores = [(10.0, 0.9), (11.0, 0.85), (60.0, 0.5)];
+        assert_eq!(pick_scenes(&scores, 0.3, 0.0, 100.0, 2), vec![10.0, 60.0]);
+    }
+
+    #[test]
+    fn pick_scenes_falls_back_to_uniform() {
+        let scores = [(30.0, 0.9)];
+        let picks = pick_scenes(&scores, 0.3, 0.0, 100.0, 4);
+        assert_eq!(picks.len(), 4);
+        assert!(picks.contains(&30.0));
+        assert!(picks.contains(&0.0));
+    }
+
+    #[test]
+    fn pick_scenes_ignores_cuts_before_start() {
+        let scores = [(2.0, 0.9), (40.0, 0.6)];
+        let picks = pick_scenes(&scores, 0.3, 5.0, 100.0, 1);
+        assert_eq!(picks, vec![40.0]);
+    }
+}
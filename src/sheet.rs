@@ -0,0 +1,1266 @@
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Mutex};
+
+use image::{DynamicImage, GenericImageView, RgbImage, RgbaImage};
+use rayon::prelude::*;
+use serde::Serialize;
+
+use crate::animation::{AnimationFrame, AnimationOptions};
+use crate::encode::{self, EncodeOptions, OutputFormat};
+use crate::error::{Result, ThumbsdownError};
+use crate::grid::{Thumbnail, TimestampOverlay};
+use crate::layout::{self, Aspect, Columns, SheetSize};
+use crate::manifest::{self, Manifest};
+use crate::quality::{self, FrameFilter};
+use crate::select::{self, Selection};
+use crate::style::Style;
+use crate::timecode::TimeSpec;
+use crate::video::{self, ExtractMode, VideoInfo};
+use crate::{batch, grid, header, vtt};
+
+/// How far a rejected frame at an explicit `at` time may be re-captured from
+/// it. The time was chosen on purpose, so only close frames stand in for it.
+const AT_NUDGE_WINDOW: f64 = 1.0;
+
+/// Builder for a thumbnail contact sheet of a single video.
+///
+/// `generate` runs the whole pipeline; `probe`, `timestamps`, `capture`,
+/// `render` and `save` expose the individual steps for callers that need
+/// to hook into it (e.g. to drive a progress bar).
+///
+/// Serializes to the parameters that affect the rendered image, which is
+/// what `fingerprint` compares to decide whether an output is stale.
+#[derive(Debug, Clone, Serialize)]
+pub struct ContactSheet {
+    #[serde(skip)]
+    video: PathBuf,
+    start: TimeSpec,
+    end: Option<TimeSpec>,
+    at: Vec<TimeSpec>,
+    every: Option<f64>,
+    thumbs: u32,
+    max_thumbs: Option<u32>,
+    rows_per_page: Option<u32>,
+    max_height: Option<u32>,
+    columns: Columns,
+    width: u32,
+    sheet_size: Option<SheetSize>,
+    aspect: Aspect,
+    timestamp_overlay: Option<TimestampOverlay>,
+    header: bool,
+    header_template: Option<String>,
+    style: Style,
+    vtt: bool,
+    selection: Selection,
+    scene_threshold: f64,
+    extract: ExtractMode,
+    frame_filter: Option<FrameFilter>,
+    #[serde(skip)]
+    jobs: usize,
+    #[serde(skip)]
+    output: Option<PathBuf>,
+    encode: EncodeOptions,
+    #[serde(skip)]
+    keep_frames: Option<PathBuf>,
+    #[serde(skip)]
+    verbose: bool,
+}
+
+/// A rendered contact sheet together with the probed video metadata.
+#[derive(Debug, Clone)]
+pub struct Sheet {
+    /// One image per page; a single page unless paging split the sheet
+    pub pages: Vec<RgbaImage>,
+    pub info: VideoInfo,
+}
+
+impl ContactSheet {
+    pub fn new(video: impl Into<PathBuf>) -> Self {
+        Self {
+            video: video.into(),
+            start: TimeSpec::Seconds(1.0),
+            end: None,
+            at: Vec::new(),
+            every: None,
+            thumbs: 20,
+            max_thumbs: None,
+            rows_per_page: None,
+            max_height: None,
+            columns: Columns::Count(5),
+            width: 320,
+            sheet_size: None,
+            aspect: Aspect::default(),
+            timestamp_overlay: None,
+            header: true,
+            header_template: None,
+            style: Style::default(),
+            vtt: false,
+            selection: Selection::default(),
+            scene_threshold: 0.3,
+            extract: ExtractMode::default(),
+            frame_filter: None,
+            jobs: 0,
+            output: None,
+            encode: EncodeOptions::default(),
+            keep_frames: None,
+            verbose: false,
+        }
+    }
+
+    /// Start time; seconds, or a `TimeSpec` relative to the duration.
+    pub fn start(mut self, start: impl Into<TimeSpec>) -> Self {
+        self.start = start.into();
+        self
+    }
+
+    /// End time; defaults to the end of the video.
+    pub fn end(mut self, end: impl Into<TimeSpec>) -> Self {
+        self.end = Some(end.into());
+        self
+    }
+
+    /// Capture exactly these times instead of choosing them; the start, end,
+    /// thumbnail count and selection mode are then ignored. The frame filter
+    /// only looks for replacements within a second of each time.
+    pub fn at<T: Into<TimeSpec>>(mut self, times: impl IntoIterator<Item = T>) -> Self {
+        self.at = times.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Number of thumbnails to capture.
+    pub fn thumbs(mut self, thumbs: u32) -> Self {
+        self.thumbs = thumbs;
+        self
+    }
+
+    /// Capture one thumbnail every `secs` seconds between the start and end
+    /// time instead of a fixed number of them.
+    pub fn every(mut self, secs: f64) -> Self {
+        self.every = Some(secs);
+        self
+    }
+
+    /// Maximum thumbnails per sheet; more are split over several pages.
+    pub fn max_thumbs(mut self, max: Option<u32>) -> Self {
+        self.max_thumbs = max;
+        self
+    }
+
+    /// Maximum grid rows per page.
+    pub fn rows_per_page(mut self, rows: Option<u32>) -> Self {
+        self.rows_per_page = rows;
+        self
+    }
+
+    /// Maximum page height in pixels, header included; at least one row is
+    /// always placed on a page.
+    pub fn max_height(mut self, height: Option<u32>) -> Self {
+        self.max_height = height;
+        self
+    }
+
+    /// Number of columns in the grid, or how to choose it (see `fit`).
+    pub fn columns(mut self, columns: impl Into<Columns>) -> Self {
+        self.columns = columns.into();
+        self
+    }
+
+    /// Thumbnail width in pixels.
+    pub fn width(mut self, width: u32) -> Self {
+        self.width = width;
+        self
+    }
+
+    /// Maximum size of the whole sheet; the thumbnail width is then chosen
+    /// to fill it (see `fit`).
+    pub fn sheet_size(mut self, size: Option<SheetSize>) -> Self {
+        self.sheet_size = size;
+        self
+    }
+
+    /// Shape `Columns::Auto` aims for unless a sheet size with a height
+    /// gives one.
+    pub fn aspect(mut self, aspect: Aspect) -> Self {
+        self.aspect = aspect;
+        self
+    }
+
+    /// Label every thumbnail with its capture time.
+    pub fn timestamp_overlay(mut self, overlay: Option<TimestampOverlay>) -> Self {
+        self.timestamp_overlay = overlay;
+        self
+    }
+
+    /// Render the metadata header above the grid.
+    pub fn header(mut self, header: bool) -> Self {
+        self.header = header;
+        self
+    }
+
+    /// Header layout with `{placeholder}`s, see `header::expand_template`.
+    pub fn header_template(mut self, template: impl Into<String>) -> Self {
+        self.header_template = Some(template.into());
+        self
+    }
+
+    /// Colors and spacing of the sheet (see `Theme` for presets).
+    pub fn style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+
+    /// Render a tightly packed sprite (no borders, header or labels) and
+    /// write a WebVTT file mapping time ranges to its tiles next to the
+    /// output, for video player seek previews.
+    pub fn vtt(mut self, vtt: bool) -> Self {
+        self.vtt = vtt;
+        self
+    }
+
+    /// How capture timestamps are chosen.
+    pub fn select(mut self, selection: Selection) -> Self {
+        self.selection = selection;
+        self
+    }
+
+    /// Minimum scene-change score (0.0-1.0) for a frame to count as a cut
+    /// in `Selection::Scenes` mode.
+    pub fn scene_threshold(mut self, threshold: f64) -> Self {
+        self.scene_threshold = threshold;
+        self
+    }
+
+    /// How frames are pulled out of the video.
+    pub fn extract(mut self, mode: ExtractMode) -> Self {
+        self.extract = mode;
+        self
+    }
+
+    /// Reject dark, blank and blurry frames and re-capture them nearby;
+    /// `None`, the default, keeps every frame as captured.
+    pub fn frame_filter(mut self, filter: Option<FrameFilter>) -> Self {
+        self.frame_filter = filter;
+        self
+    }
+
+    /// Number of frames captured and processed concurrently; 0 uses all
+    /// available cores.
+    pub fn jobs(mut self, jobs: usize) -> Self {
+        self.jobs = jobs;
+        self
+    }
+
+    /// Write the final image to this path.
+    pub fn output(mut self, path: impl Into<PathBuf>) -> Self {
+        self.output = Some(path.into());
+        self
+    }
+
+    /// Output format and encoder settings; by default the format is picked
+    /// from the output extension.
+    pub fn encode(mut self, options: EncodeOptions) -> Self {
+        self.encode = options;
+        self
+    }
+
+    /// Copy every captured frame into this directory.
+    pub fn keep_frames(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.keep_frames = Some(dir.into());
+        self
+    }
+
+    /// Log pipeline steps to stderr.
+    pub fn verbose(mut self, verbose: bool) -> Self {
+        self.verbose = verbose;
+        self
+    }
+
+    pub fn video(&self) -> &Path {
+        &self.video
+    }
+
+    pub fn generate(&self) -> Result<Sheet> {
+        let info = self.probe()?;
+        let timestamps = self.timestamps(&info)?;
+        let sheet = self.fit(&info, timestamps.len())?;
+        let thumbnails = sheet.capture(&info, &timestamps, || {})?;
+        let pages = sheet.render_pages(&info, &thumbnails)?;
+        sheet.save_pages(&pages)?;
+        sheet.write_vtt(&info, &thumbnails)?;
+        Ok(Sheet { pages, info })
+    }
+
+    /// The sheet with a fixed column count and thumbnail width for `count`
+    /// thumbnails. `Columns::Rows` spreads them over that many rows and
+    /// `Columns::Auto` picks the count that best fills a sheet size with a
+    /// height, or else brings the sheet closest to the aspect ratio. With a
+    /// sheet size the width is chosen to fill it. `generate` does this once
+    /// the timestamps are known; callers running the steps themselves should
+    /// fit before `capture`.
+    pub fn fit(&self, info: &VideoInfo, count: usize) -> Result<Self> {
+        if let (Columns::Count(_), None) = (self.columns, self.sheet_size) {
+            return Ok(self.clone());
+        }
+
+        let per_page = self
+            .max_thumbs
+            .map_or(count, |max| count.min(max.max(1) as usize));
+        // Paged sheets carry a page line in their header
+        let paged = per_page < count || self.rows_per_page.is_some() || self.max_height.is_some();
+        let header_height = self
+            .header_image(info, paged.then_some((1, 1)))?
+            .map_or(0, |header| header.height());
+        let contents = layout::Contents {
+            frame: info.display_size(),
+            count: per_page,
+            max_rows: self.rows_per_page,
+            header_height,
+            aspect: self.aspect,
+        };
+        let style = self.effective_style();
+        let fit = match self.sheet_size {
+            Some(size) => layout::fit(size, self.columns, &contents, &style)?,
+            None => layout::Fit {
+                columns: layout::columns(self.columns, self.width, &contents, &style),
+                width: self.width,
+            },
+        };
+        if self.verbose {
+            eprintln!(
+                "Laying out {} columns of {}px thumbnails",
+                fit.columns, fit.width
+            );
+        }
+        Ok(self.clone().columns(fit.columns).width(fit.width))
+    }
+
+    pub fn probe(&self) -> Result<VideoInfo> {
+        let info = video::probe(&self.video)?;
+        if self.verbose {
+            let (width, height) = info.display_size();
+            eprintln!(
+                "Video: {} ({width}x{height}, {}, {:.2} fps, {:.1}s)",
+                info.filename, info.codec, info.fps, info.duration
+            );
+        }
+        Ok(info)
+    }
+
+    /// Capture times between the start and end time, chosen according to
+    /// the selection mode, or the explicit `at` times in ascending order.
+    pub fn timestamps(&self, info: &VideoInfo) -> Result<Vec<f64>> {
+        let timestamps = if !self.at.is_empty() {
+            let mut timestamps = self
+                .at
+                .iter()
+                .map(|spec| spec.resolve(info.duration))
+                .collect::<Result<Vec<f64>>>()?;
+            timestamps.sort_by(f64::total_cmp);
+            timestamps.dedup();
+            timestamps
+        } else {
+            let (start, end) = self.range(info)?;
+            match (self.every, self.selection) {
+                (Some(interval), _) if !(interval.is_finite() && interval > 0.0) => {
+                    return Err(ThumbsdownError::InvalidInterval(interval.to_string()));
+                }
+                (Some(interval), _) => select::every(start, end, interval),
+                (None, Selection::Uniform) => select::uniform(start, end, self.thumbs),
+                (None, Selection::Scenes) => {
+                    if self.verbose {
+                        eprintln!("Detecting scene changes");
+                    }
+                    let scores = video::scene_scores(&self.video)?;
+                    if self.verbose {
+                        let cuts = scores
+                            .iter()
+                            .filter(|(_, score)| *score >= self.scene_threshold)
+                            .count();
+                        eprintln!("Found {cuts} scene changes");
+                    }
+                    select::pick_scenes(&scores, self.scene_threshold, start, end, self.thumbs)
+                }
+            }
+        };
+
+        if self.verbose {
+            let list: Vec<String> = timestamps.iter().map(|t| format!("{t:.1}s")).collect();
+            eprintln!("Timestamps: {}", list.join(", "));
+        }
+
+        Ok(timestamps)
+    }
+
+    /// Captures a frame at every timestamp and turns it into a bordered,
+    /// resized thumbnail. Work is spread over `jobs` threads; the result is
+    /// always in timestamp order. `on_frame` is called after each frame.
+    pub fn capture(
+        &self,
+        info: &VideoInfo,
+        timestamps: &[f64],
+        on_frame: impl Fn() + Sync,
+    ) -> Result<Vec<Thumbnail>> {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(self.jobs)
+            .build()?;
+        if self.verbose {
+            eprintln!("Capturing with {} jobs", pool.current_num_threads());
+        }
+
+        // ffmpeg applies the rotation; scaling to the display size squares the pixels
+        let (width, height) = info.display_size();
+        match self.extract {
+            ExtractMode::PerFrame => pool.install(|| {
+                timestamps
+                    .par_iter()
+                    .enumerate()
+                    .map(|(i, &time)| {
+                        let frame = video::capture_frame(&self.video, time, width, height)?;
+                        let (time, frame) = self.checked_frame(info, timestamps, i, frame)?;
+                        let thumb = self.finish_frame(info, i, time, frame)?;
+                        on_frame();
+                        Ok(thumb)
+                    })
+                    .collect()
+            }),
+            ExtractMode::SinglePass => {
+                if self.verbose {
+                    eprintln!("Extracting {} frames in a single pass", timestamps.len());
+                }
+                // Decoding runs on this thread and the workers take frames off a
+                // bounded queue, so decoding waits instead of piling up frames
+                // while every worker is busy
+                let jobs = pool.current_num_threads();
+                let (sender, receiver) = mpsc::sync_channel::<(usize, RgbImage)>(jobs);
+                let receiver = Mutex::new(receiver);
+                let results = Mutex::new(Vec::with_capacity(timestamps.len()));
+                pool.in_place_scope(|s| {
+                    for _ in 0..jobs {
+                        s.spawn(|_| loop {
+                            // The queue closes once decoding is done and drained
+                            let next = receiver.lock().expect("receiver lock").recv();
+                            let Ok((i, frame)) = next else {
+                                break;
+                            };
+                            let thumb = self
+                                .checked_frame(info, timestamps, i, frame)
+                                .and_then(|(time, frame)| self.finish_frame(info, i, time, frame));
+                            on_frame();
+                            results.lock().expect("results lock").push((i, thumb));
+                        });
+                    }
+                    let decoded = video::capture_frames(
+                        &self.video,
+                        timestamps,
+                        info.fps,
+                        width,
+                        height,
+                        |i, frame| {
+                            sender
+                                .send((i, frame))
+                                .expect("workers receive until the queue closes");
+                            Ok(())
+                        },
+                    );
+                    drop(sender);
+                    decoded
+                })?;
+
+                let mut results = results.into_inner().expect("results lock");
+                results.sort_by_key(|(i, _)| *i);
+                results.into_iter().map(|(_, thumb)| thumb).collect()
+            }
+        }
+    }
+
+    /// Runs the frame filter on a captured frame. Rejected frames are
+    /// re-captured at nudged times within their slot; if every attempt is
+    /// rejected the highest-contrast candidate is kept.
+    fn checked_frame(
+        &self,
+        info: &VideoInfo,
+        timestamps: &[f64],
+        index: usize,
+        frame: RgbImage,
+    ) -> Result<(f64, RgbImage)> {
+        let time = timestamps[index];
+        let Some(filter) = self.frame_filter else {
+            return Ok((time, frame));
+        };
+        let stats = quality::measure(&frame);
+        let Some(rejection) = filter.check(&stats) else {
+            return Ok((time, frame));
+        };
+        if self.verbose {
+            eprintln!("Rejected frame at {time:.1}s: {rejection}");
+        }
+
+        let (lower, upper) = self.slot(info, timestamps, index);
+        let (width, height) = info.display_size();
+        let mut best = (stats.contrast, time, frame);
+        for retry in quality::nudges(time, lower, upper, filter.retries) {
+            let candidate = match video::capture_frame(&self.video, retry, width, height) {
+                Ok(candidate) => candidate,
+                Err(e) => {
+                    if self.verbose {
+                        eprintln!("Retry at {retry:.1}s failed: {e}");
+                    }
+                    continue;
+                }
+            };
+            let stats = quality::measure(&candidate);
+            match filter.check(&stats) {
+                None => {
+                    if self.verbose {
+                        eprintln!("Accepted replacement frame at {retry:.1}s");
+                    }
+                    return Ok((retry, candidate));
+                }
+                Some(rejection) => {
+                    if self.verbose {
+                        eprintln!("Rejected frame at {retry:.1}s: {rejection}");
+                    }
+                    if stats.contrast > best.0 {
+                        best = (stats.contrast, retry, candidate);
+                    }
+                }
+            }
+        }
+
+        if self.verbose {
+            eprintln!(
+                "No acceptable frame near {time:.1}s, keeping frame at {:.1}s",
+                best.1
+            );
+        }
+        Ok((best.1, best.2))
+    }
+
+    /// The start and end time resolved against the video's duration.
+    fn range(&self, info: &VideoInfo) -> Result<(f64, f64)> {
+        let start = self.start.resolve(info.duration)?;
+        let end = match self.end {
+            Some(end) => end.resolve(info.duration)?,
+            None => info.duration,
+        };
+        if start >= end {
+            return Err(ThumbsdownError::EmptyTimeRange {
+                start: self.start.to_string(),
+                end: self
+                    .end
+                    .map_or_else(|| "end of video".to_string(), |e| e.to_string()),
+            });
+        }
+        Ok((start, end))
+    }
+
+    /// The range a timestamp may be nudged within without crossing halfway
+    /// to its neighbours or leaving the start-end range. Explicit `at` times
+    /// also stay within `AT_NUDGE_WINDOW` of where they were asked for.
+    fn slot(&self, info: &VideoInfo, timestamps: &[f64], index: usize) -> (f64, f64) {
+        let time = timestamps[index];
+        let (start, end) = if self.at.is_empty() {
+            self.range(info).unwrap_or((0.0, info.duration))
+        } else {
+            (
+                (time - AT_NUDGE_WINDOW).max(0.0),
+                (time + AT_NUDGE_WINDOW).min(info.duration),
+            )
+        };
+        let lower = match index {
+            0 => start,
+            _ => ((timestamps[index - 1] + time) / 2.0).max(start),
+        };
+        let upper = match timestamps.get(index + 1) {
+            Some(next) => ((time + next) / 2.0).min(end),
+            None => end,
+        };
+        (lower.min(time), upper.max(time))
+    }
+
+    fn finish_frame(
+        &self,
+        info: &VideoInfo,
+        index: usize,
+        time: f64,
+        frame: RgbImage,
+    ) -> Result<Thumbnail> {
+        let frame = DynamicImage::ImageRgb8(frame);
+
+        if let Some(ref keep_dir) = self.keep_frames {
+            let dest = keep_dir.join(format!("frame-{index:04}.png"));
+            frame.save(&dest)?;
+            if self.verbose {
+                eprintln!("Saved frame to {}", dest.display());
+            }
+        }
+
+        if self.verbose {
+            eprintln!("Captured frame at {time:.1}s");
+        }
+
+        Ok(Thumbnail {
+            image: grid::process_thumbnail(&frame, self.width, &self.effective_style()),
+            time,
+            frame: (time * info.fps).round() as u64,
+        })
+    }
+
+    pub fn render(&self, info: &VideoInfo, thumbnails: &[Thumbnail]) -> Result<RgbaImage> {
+        self.render_page(info, thumbnails, None)
+    }
+
+    fn render_page(
+        &self,
+        info: &VideoInfo,
+        thumbnails: &[Thumbnail],
+        page: Option<(usize, usize)>,
+    ) -> Result<RgbaImage> {
+        let style = self.effective_style();
+        let overlay = self.timestamp_overlay.as_ref().filter(|_| !self.vtt);
+        let grid_image = grid::compose_grid(thumbnails, self.column_count(), overlay, &style)?;
+        let header_image = self.header_image(info, page)?;
+        Ok(grid::assemble_final(
+            header_image.as_ref(),
+            &grid_image,
+            &style,
+        ))
+    }
+
+    /// The header `render_page` puts above a page, if the sheet has one.
+    fn header_image(
+        &self,
+        info: &VideoInfo,
+        page: Option<(usize, usize)>,
+    ) -> Result<Option<RgbaImage>> {
+        if !self.header || self.vtt {
+            return Ok(None);
+        }
+        let style = self.effective_style();
+        header::render_header(info, self.header_template.as_deref(), page, &style).map(Some)
+    }
+
+    /// The fixed column count; an unfitted `Columns::Auto` or
+    /// `Columns::Rows` lays out a single column.
+    fn column_count(&self) -> u32 {
+        match self.columns {
+            Columns::Count(count) => count.max(1),
+            Columns::Auto | Columns::Rows(_) => 1,
+        }
+    }
+
+    /// The configured style, packed tightly in `vtt` mode.
+    fn effective_style(&self) -> Style {
+        if self.vtt {
+            self.style.packed()
+        } else {
+            self.style
+        }
+    }
+
+    /// Renders the thumbnails as one sheet, or as several pages when
+    /// `max_thumbs`, `rows_per_page` or `max_height` call for it. Each page
+    /// gets its own header with a page indicator.
+    pub fn render_pages(
+        &self,
+        info: &VideoInfo,
+        thumbnails: &[Thumbnail],
+    ) -> Result<Vec<RgbaImage>> {
+        let per_page = match self.page_size(info, thumbnails)? {
+            Some(per_page) if per_page < thumbnails.len() => per_page,
+            _ => return Ok(vec![self.render(info, thumbnails)?]),
+        };
+        let pages: Vec<&[Thumbnail]> = thumbnails.chunks(per_page).collect();
+        pages
+            .iter()
+            .enumerate()
+            .map(|(i, page)| self.render_page(info, page, Some((i + 1, pages.len()))))
+            .collect()
+    }
+
+    /// Thumbnails per page allowed by the paging limits, if any is set.
+    fn page_size(&self, info: &VideoInfo, thumbnails: &[Thumbnail]) -> Result<Option<usize>> {
+        let columns = self.column_count() as usize;
+        let mut rows = self.rows_per_page.map(|rows| rows.max(1) as usize);
+
+        if let (Some(max_height), Some(first)) = (self.max_height, thumbnails.first()) {
+            let style = self.effective_style();
+            // Measured with the page line, as a paged header carries one
+            let header_height = self
+                .header_image(info, Some((1, 1)))?
+                .map_or(0, |header| header.height());
+            let available = max_height.saturating_sub(header_height + style.margin * 2);
+            // Each row after the first also takes a gutter
+            let row_height = first.image.height() + style.gutter;
+            let fit = ((available + style.gutter) / row_height.max(1)).max(1) as usize;
+            rows = Some(rows.map_or(fit, |rows| rows.min(fit)));
+        }
+
+        let by_rows = rows.map(|rows| rows * columns);
+        let by_count = self.max_thumbs.map(|max| max.max(1) as usize);
+        Ok(match (by_rows, by_count) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        })
+    }
+
+    /// Writes the pages to the configured output path, if any. A single page
+    /// or a PDF goes to the path itself, other pages to numbered files next
+    /// to it (see `batch::page_path`).
+    pub fn save_pages(&self, pages: &[RgbaImage]) -> Result<()> {
+        let Some(ref output) = self.output else {
+            return Ok(());
+        };
+        if let [image] = pages {
+            return self.save(image);
+        }
+        if encode::resolve_format(output, self.encode.format)? == OutputFormat::Pdf {
+            encode::save_pdf(pages, output, &self.encode)?;
+            if self.verbose {
+                eprintln!("Saved {} pages to {}", pages.len(), output.display());
+            }
+            return Ok(());
+        }
+        for (i, page) in pages.iter().enumerate() {
+            let path = batch::page_path(output, i + 1);
+            encode::save(page, &path, &self.encode)?;
+            if self.verbose {
+                eprintln!(
+                    "Saved page {} of {} to {}",
+                    i + 1,
+                    pages.len(),
+                    path.display()
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// In `vtt` mode, writes the cue file for the saved sprite pages next to
+    /// the output. Each tile covers the time until the next one; the first
+    /// also covers the start of the video.
+    pub fn write_vtt(&self, info: &VideoInfo, thumbnails: &[Thumbnail]) -> Result<()> {
+        let (true, Some(output)) = (self.vtt, &self.output) else {
+            return Ok(());
+        };
+        let per_page = match self.page_size(info, thumbnails)? {
+            Some(per_page) if per_page < thumbnails.len() => Some(per_page),
+            _ => None,
+        };
+        let columns = self.column_count() as usize;
+
+        let cues: Vec<vtt::Cue> = thumbnails
+            .iter()
+            .enumerate()
+            .map(|(i, thumb)| {
+                let (sprite, slot) = match per_page {
+                    Some(per_page) => (batch::page_path(output, i / per_page + 1), i % per_page),
+                    None => (output.clone(), i),
+                };
+                let (width, height) = thumb.image.dimensions();
+                vtt::Cue {
+                    start: if i == 0 { 0.0 } else { thumb.time },
+                    end: thumbnails
+                        .get(i + 1)
+                        .map_or(info.duration, |next| next.time),
+                    url: vtt::sprite_url(&sprite),
+                    x: (slot % columns) as u32 * width,
+                    y: (slot / columns) as u32 * height,
+                    width,
+                    height,
+                }
+            })
+            .collect();
+
+        let path = vtt::vtt_path(output);
+        std::fs::write(&path, vtt::render(&cues))?;
+        if self.verbose {
+            eprintln!("Saved cues to {}", path.display());
+        }
+        Ok(())
+    }
+
+    /// Describes the rendered `pages` of `thumbnails`: the video, the
+    /// parameters, the saved files and where each tile sits on its page.
+    pub fn manifest(
+        &self,
+        info: &VideoInfo,
+        thumbnails: &[Thumbnail],
+        pages: &[RgbaImage],
+    ) -> Result<Manifest> {
+        let per_page = match self.page_size(info, thumbnails)? {
+            Some(per_page) if per_page < thumbnails.len() => per_page,
+            _ => thumbnails.len().max(1),
+        };
+        let chunks: Vec<&[Thumbnail]> = thumbnails.chunks(per_page).collect();
+
+        let style = self.effective_style();
+        let mut tiles = Vec::with_capacity(thumbnails.len());
+        for (page, chunk) in chunks.iter().enumerate() {
+            // Same header as `render_pages` gives the page
+            let indicator = (chunks.len() > 1).then_some((page + 1, chunks.len()));
+            let header_size = self
+                .header_image(info, indicator)?
+                .map_or((0, 0), |header| header.dimensions());
+            let (offsets, grid_size) = grid::layout(chunk, self.column_count(), &style);
+            let (grid_x, grid_y) = grid::grid_origin(header_size, grid_size, &style);
+            for (thumb, (x, y)) in chunk.iter().zip(offsets) {
+                tiles.push(manifest::Tile {
+                    page,
+                    time: thumb.time,
+                    frame: thumb.frame,
+                    x: grid_x + x,
+                    y: grid_y + y,
+                    width: thumb.image.width(),
+                    height: thumb.image.height(),
+                });
+            }
+        }
+
+        let pdf = match self.output {
+            Some(ref output) => {
+                encode::resolve_format(output, self.encode.format)? == OutputFormat::Pdf
+            }
+            None => false,
+        };
+        let pages = pages
+            .iter()
+            .enumerate()
+            .map(|(i, image)| manifest::Page {
+                file: match self.output {
+                    Some(ref output) if pages.len() > 1 && !pdf => batch::page_path(output, i + 1),
+                    Some(ref output) => output.clone(),
+                    None => PathBuf::new(),
+                },
+                width: image.width(),
+                height: image.height(),
+            })
+            .collect();
+
+        Ok(Manifest {
+            video: info.clone(),
+            params: self.clone(),
+            pages,
+            tiles,
+        })
+    }
+
+    /// Frames for an animated preview of `thumbnails`: each thumbnail without
+    /// its border, followed by the next `burst - 1` frames of the video when
+    /// the options ask for bursts. The bursts are captured on `jobs` threads.
+    pub fn animation_frames(
+        &self,
+        info: &VideoInfo,
+        thumbnails: &[Thumbnail],
+        options: &AnimationOptions,
+    ) -> Result<Vec<AnimationFrame>> {
+        let border = self.effective_style().border_width;
+        let delays = options.burst_delays(info.fps);
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(self.jobs)
+            .build()?;
+        if self.verbose && delays.len() > 1 {
+            eprintln!(
+                "Capturing bursts of {} frames for the animation",
+                delays.len()
+            );
+        }
+
+        let bursts: Vec<Vec<AnimationFrame>> = pool.install(|| {
+            thumbnails
+                .par_iter()
+                .map(|thumb| {
+                    let first = grid::strip_border(&thumb.image, border);
+                    let (width, height) = first.dimensions();
+                    let mut images = vec![first];
+                    if delays.len() > 1 {
+                        let burst = video::capture_burst(
+                            &self.video,
+                            thumb.time,
+                            delays.len() as u32,
+                            width,
+                            height,
+                        )?;
+                        images.extend(burst.into_iter().skip(1));
+                    }
+                    // A burst cut short by the end of the video still ends on the pause
+                    let last = images.len() - 1;
+                    Ok(images
+                        .into_iter()
+                        .enumerate()
+                        .map(|(i, image)| AnimationFrame {
+                            image,
+                            delay_ms: if i == last {
+                                options.delay_ms
+                            } else {
+                                delays[i]
+                            },
+                        })
+                        .collect())
+                })
+                .collect::<Result<_>>()
+        })?;
+        Ok(bursts.into_iter().flatten().collect())
+    }
+
+    /// Writes the image to the configured output path, if any.
+    pub fn save(&self, image: &RgbaImage) -> Result<()> {
+        if let Some(ref output) = self.output {
+            encode::save(image, output, &self.encode)?;
+            if self.verbose {
+                eprintln!("Saved to {}", output.display());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_info(duration: f64) -> VideoInfo {
+        VideoInfo {
+            filename: "test_video.mp4".to_string(),
+            duration,
+            width: 1920,
+            height: 1080,
+            codec: "h264".to_string(),
+            fps: 25.0,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn timestamps_are_evenly_spaced_from_start() {
+        let sheet = ContactSheet::new("video.mp4").start(0.0).thumbs(4);
+        assert_eq!(
+            sheet.timestamps(&test_info(100.0)).expect("timestamps"),
+            vec![0.0, 25.0, 50.0, 75.0]
+        );
+    }
+
+    #[test]
+    fn timestamps_respect_start_offset() {
+        let sheet = ContactSheet::new("video.mp4").start(10.0).thumbs(3);
+        assert_eq!(
+            sheet.timestamps(&test_info(100.0)).expect("timestamps"),
+            vec![10.0, 40.0, 70.0]
+        );
+    }
+
+    #[test]
+    fn timestamps_reject_start_past_end_of_video() {
+        let sheet = ContactSheet::new("video.mp4").start(50.0).thumbs(3);
+        assert!(matches!(
+            sheet.timestamps(&test_info(10.0)),
+            Err(ThumbsdownError::TimeOutOfRange { .. })
+        ));
+    }
+
+    #[test]
+    fn timestamps_stay_within_end_offset() {
+        let sheet = ContactSheet::new("video.mp4")
+            .start(TimeSpec::Percent(10.0))
+            .end(-20.0)
+            .thumbs(4);
+        assert_eq!(
+            sheet.timestamps(&test_info(100.0)).expect("timestamps"),
+            vec![10.0, 27.5, 45.0, 62.5]
+        );
+    }
+
+    #[test]
+    fn timestamps_follow_interval() {
+        let sheet = ContactSheet::new("video.mp4")
+            .start(0.0)
+            .thumbs(3)
+            .every(30.0);
+        assert_eq!(
+            sheet.timestamps(&test_info(100.0)).expect("timestamps"),
+            vec![0.0, 30.0, 60.0, 90.0]
+        );
+    }
+
+    #[test]
+    fn timestamps_reject_invalid_interval() {
+        for interval in [0.0, -5.0, f64::NAN, f64::INFINITY] {
+            let sheet = ContactSheet::new("video.mp4").every(interval);
+            assert!(matches!(
+                sheet.timestamps(&test_info(100.0)),
+                Err(ThumbsdownError::InvalidInterval(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn render_pages_splits_at_max_thumbs() {
+        let thumbnails: Vec<Thumbnail> = (0..5)
+            .map(|i| Thumbnail {
+                image: DynamicImage::new_rgb8(16, 9),
+                time: i as f64,
+                frame: i,
+            })
+            .collect();
+        let sheet = ContactSheet::new("video.mp4")
+            .columns(2)
+            .header(false)
+            .max_thumbs(Some(2));
+        let pages = sheet
+            .render_pages(&test_info(10.0), &thumbnails)
+            .expect("render");
+        assert_eq!(pages.len(), 3);
+        assert_eq!(pages[0].dimensions(), pages[1].dimensions());
+        assert!(pages[2].width() < pages[0].width());
+    }
+
+    #[test]
+    fn render_pages_fits_rows_to_height() {
+        let thumbnails: Vec<Thumbnail> = (0..6)
+            .map(|i| Thumbnail {
+                image: DynamicImage::new_rgb8(16, 10),
+                time: i as f64,
+                frame: i,
+            })
+            .collect();
+        let sheet = ContactSheet::new("video.mp4")
+            .columns(2)
+            .header(false)
+            .max_height(Some(25));
+        let pages = sheet
+            .render_pages(&test_info(10.0), &thumbnails)
+            .expect("render");
+        assert_eq!(pages.len(), 2);
+        assert_eq!(pages[0].height(), 20);
+    }
+
+    #[test]
+    fn rows_per_page_and_max_thumbs_take_the_smaller_page() {
+        let sheet = ContactSheet::new("video.mp4")
+            .columns(4)
+            .rows_per_page(Some(3))
+            .max_thumbs(Some(10));
+        let size = sheet.page_size(&test_info(10.0), &[]).expect("page size");
+        assert_eq!(size, Some(10));
+        let size = sheet
+            .rows_per_page(Some(2))
+            .page_size(&test_info(10.0), &[])
+            .expect("page size");
+        assert_eq!(size, Some(8));
+    }
+
+    #[test]
+    fn write_vtt_maps_times_to_tiles() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let thumbnails: Vec<Thumbnail> = [5.0, 35.0, 65.0]
+            .iter()
+            .map(|&time| Thumbnail {
+                image: DynamicImage::new_rgb8(160, 90),
+                time,
+                frame: 0,
+            })
+            .collect();
+        let sheet = ContactSheet::new("video.mp4")
+            .columns(2)
+            .vtt(true)
+            .output(dir.path().join("sprite.jpg"));
+        sheet
+            .write_vtt(&test_info(100.0), &thumbnails)
+            .expect("write");
+
+        let cues = std::fs::read_to_string(dir.path().join("sprite.vtt")).expect("read");
+        assert!(cues.starts_with("WEBVTT\n"));
+        assert!(cues.contains("00:00:00.000 --> 00:00:35.000\nsprite.jpg#xywh=0,0,160,90"));
+        assert!(cues.contains("00:01:05.000 --> 00:01:40.000\nsprite.jpg#xywh=0,90,160,90"));
+    }
+
+    #[test]
+    fn animation_frames_reuse_thumbnails_without_border() {
+        let info = test_info(100.0);
+        let sheet = ContactSheet::new("video.mp4").width(200);
+        let frame = DynamicImage::new_rgb8(info.width, info.height);
+        let thumbnails: Vec<Thumbnail> = [10.0, 20.0]
+            .iter()
+            .map(|&time| Thumbnail {
+                image: grid::process_thumbnail(&frame, 200, &Style::default()),
+                time,
+                frame: 0,
+            })
+            .collect();
+        let options = AnimationOptions {
+            delay_ms: 300,
+            ..Default::default()
+        };
+
+        let frames = sheet
+            .animation_frames(&info, &thumbnails, &options)
+            .expect("frames");
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].image.width(), 196);
+        assert!(frames.iter().all(|frame| frame.delay_ms == 300));
+    }
+
+    #[test]
+    fn manifest_rectangles_locate_tiles_on_pages() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let info = test_info(100.0);
+        let colors = [[255, 0, 0, 255], [0, 255, 0, 255], [0, 0, 255, 255]];
+        let thumbnails: Vec<Thumbnail> = colors
+            .iter()
+            .enumerate()
+            .map(|(i, &color)| Thumbnail {
+                image: DynamicImage::ImageRgba8(RgbaImage::from_pixel(40, 30, image::Rgba(color))),
+                time: i as f64 * 10.0,
+                frame: i as u64 * 250,
+            })
+            .collect();
+        let sheet = ContactSheet::new("video.mp4")
+            .columns(2)
+            .max_thumbs(Some(2))
+            .output(dir.path().join("sheet.png"));
+        let pages = sheet.render_pages(&info, &thumbnails).expect("render");
+        let manifest = sheet
+            .manifest(&info, &thumbnails, &pages)
+            .expect("manifest");
+
+        assert_eq!(manifest.pages.len(), 2);
+        assert_eq!(manifest.pages[1].file, dir.path().join("sheet-002.png"));
+        assert_eq!(manifest.tiles.len(), 3);
+        assert_eq!(manifest.tiles[2].page, 1);
+        assert!(manifest.tiles[0].y > 0, "tiles sit below the header");
+        for (tile, color) in manifest.tiles.iter().zip(colors) {
+            let page = &pages[tile.page];
+            let centre = page.get_pixel(tile.x + tile.width / 2, tile.y + tile.height / 2);
+            assert_eq!(centre.0, color);
+            assert_eq!(page.get_pixel(tile.x, tile.y).0, color);
+        }
+    }
+
+    #[test]
+    fn vtt_sprite_has_no_header() {
+        let thumbnails = vec![Thumbnail {
+            image: DynamicImage::new_rgb8(160, 90),
+            time: 0.0,
+            frame: 0,
+        }];
+        let sheet = ContactSheet::new("video.mp4").vtt(true);
+        let image = sheet.render(&test_info(10.0), &thumbnails).expect("render");
+        assert_eq!(image.dimensions(), (160, 90));
+    }
+
+    #[test]
+    fn timestamps_reject_empty_range() {
+        let sheet = ContactSheet::new("video.mp4").start(60.0).end(30.0);
+        assert!(matches!(
+            sheet.timestamps(&test_info(100.0)),
+            Err(ThumbsdownError::EmptyTimeRange { .. })
+        ));
+    }
+
+    #[test]
+    fn explicit_times_bypass_selection() {
+        let sheet = ContactSheet::new("video.mp4")
+            .thumbs(10)
+            .at([TimeSpec::FromEnd(10.0), TimeSpec::Seconds(5.0)]);
+        assert_eq!(
+            sheet.timestamps(&test_info(100.0)).expect("timestamps"),
+            vec![5.0, 90.0]
+        );
+    }
+
+    #[test]
+    fn slot_spans_halfway_to_neighbours() {
+        let sheet = ContactSheet::new("video.mp4").start(0.0);
+        let info = test_info(100.0);
+        let timestamps = [0.0, 20.0, 40.0];
+        assert_eq!(sheet.slot(&info, &timestamps, 0), (0.0, 10.0));
+        assert_eq!(sheet.slot(&info, &timestamps, 1), (10.0, 30.0));
+        assert_eq!(sheet.slot(&info, &timestamps, 2), (30.0, 100.0));
+    }
+
+    #[test]
+    fn slot_keeps_explicit_times_close() {
+        let sheet = ContactSheet::new("video.mp4").at([TimeSpec::Percent(50.0)]);
+        let info = test_info(7200.0);
+        assert_eq!(sheet.slot(&info, &[3600.0], 0), (3599.0, 3601.0));
+
+        // Neighbours closer than the window still bound the slot
+        let sheet = sheet.at([10.0, 10.5, 7199.5]);
+        let timestamps = [10.0, 10.5, 7199.5];
+        assert_eq!(sheet.slot(&info, &timestamps, 0), (9.0, 10.25));
+        assert_eq!(sheet.slot(&info, &timestamps, 1), (10.25, 11.5));
+        assert_eq!(sheet.slot(&info, &timestamps, 2), (7198.5, 7200.0));
+    }
+
+    #[test]
+    fn checked_frame_without_filter_keeps_frame() {
+        // Off unless asked for
+        let sheet = ContactSheet::new("video.mp4");
+        let black = RgbImage::new(16, 9);
+        let (time, _) = sheet
+            .checked_frame(&test_info(100.0), &[5.0], 0, black)
+            .expect("checked");
+        assert_eq!(time, 5.0);
+    }
+
+    #[test]
+    fn capture_without_timestamps_is_empty() {
+        let sheet = ContactSheet::new("video.mp4").jobs(2);
+        let thumbs = sheet
+            .capture(&test_info(10.0), &[], || {})
+            .expect("capture");
+        assert!(thumbs.is_empty());
+    }
+
+    #[test]
+    fn single_pass_capture_failure_releases_workers() {
+        // Workers wait on the frame queue; a failed decode must still end them
+        let sheet = ContactSheet::new("nonexistent_video_xyz.mp4")
+            .extract(ExtractMode::SinglePass)
+            .jobs(1);
+        let result = sheet.capture(&test_info(10.0), &[1.0, 2.0], || {});
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn fit_keeps_rendered_sheet_within_size() {
+        let info = test_info(100.0);
+        let sheet = ContactSheet::new("video.mp4")
+            .columns(Columns::Auto)
+            .sheet_size(Some("1280x720".parse().expect("size")));
+        let fitted = sheet.fit(&info, 12).expect("fit");
+        assert!(matches!(fitted.columns, Columns::Count(columns) if columns > 1));
+
+        // Same aspect as the video; a full-size frame only slows the resize down
+        let frame = DynamicImage::ImageRgb8(RgbImage::new(192, 108));
+        let thumbnails: Vec<Thumbnail> = (0..12)
+            .map(|i| Thumbnail {
+                image: grid::process_thumbnail(&frame, fitted.width, &fitted.style),
+                time: i as f64,
+                frame: i,
+            })
+            .collect();
+        let page = fitted.render(&info, &thumbnails).expect("render");
+        assert!(page.width() <= 1280 && page.height() <= 720);
+        assert!(
+            page.width() > 1200 || page.height() > 680,
+            "fills the canvas"
+        );
+
+        // Without a size the columns follow the aspect ratio instead
+        let unbounded = ContactSheet::new("video.mp4")
+            .columns(Columns::Auto)
+            .aspect("1:1".parse().expect("aspect"))
+            .header(false);
+        let square = unbounded.fit(&info, 16).expect("fit");
+        // Three columns of six rows come closer to square than four of four
+        assert_eq!(square.columns, Columns::Count(3));
+        assert_eq!(square.width, 320);
+    }
+
+    #[test]
+    fn generate_missing_video_returns_error() {
+        let result = ContactSheet::new("/nonexistent_video_xyz.mp4").generate();
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,267 @@
+use std::fmt;
+use std::str::FromStr;
+
+use clap::ValueEnum;
+use image::Rgba;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Result, ThumbsdownError};
+
+pub const DEFAULT_BORDER_WIDTH: u32 = 2;
+
+/// An sRGB color with alpha, parsed from `#rrggbb`, `#rrggbbaa`, `#rgb`
+/// (the `#` is optional) or one of a few names such as `white` or
+/// `transparent`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Color(pub [u8; 4]);
+
+impl Color {
+    pub const WHITE: Color = Color::rgb(255, 255, 255);
+    pub const BLACK: Color = Color::rgb(0, 0, 0);
+    pub const TRANSPARENT: Color = Color([0, 0, 0, 0]);
+
+    /// An opaque color.
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Color([r, g, b, 255])
+    }
+
+    pub fn rgba(self) -> Rgba<u8> {
+        Rgba(self.0)
+    }
+
+    pub fn is_opaque(self) -> bool {
+        self.0[3] == u8::MAX
+    }
+}
+
+const NAMED_COLORS: &[(&str, Color)] = &[
+    ("white", Color::WHITE),
+    ("black", Color::BLACK),
+    ("gray", Color::rgb(128, 128, 128)),
+    ("grey", Color::rgb(128, 128, 128)),
+    ("transparent", Color::TRANSPARENT),
+];
+
+impl FromStr for Color {
+    type Err = ThumbsdownError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || ThumbsdownError::InvalidColor(s.to_string());
+        let text = s.trim();
+        if let Some(&(_, color)) = NAMED_COLORS
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(text))
+        {
+            return Ok(color);
+        }
+
+        let hex = text.strip_prefix('#').unwrap_or(text);
+        if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(invalid());
+        }
+        let channel = |digits: &str| u8::from_str_radix(digits, 16).map_err(|_| invalid());
+        match hex.len() {
+            6 | 8 => Ok(Color([
+                channel(&hex[0..2])?,
+                channel(&hex[2..4])?,
+                channel(&hex[4..6])?,
+                hex.get(6..8).map_or(Ok(u8::MAX), channel)?,
+            ])),
+            // #rgb doubles each digit
+            3 => Ok(Color::rgb(
+                channel(&hex[0..1])? * 17,
+                channel(&hex[1..2])? * 17,
+                channel(&hex[2..3])? * 17,
+            )),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [r, g, b, a] = self.0;
+        write!(f, "#{r:02x}{g:02x}{b:02x}")?;
+        if !self.is_opaque() {
+            write!(f, "{a:02x}")?;
+        }
+        Ok(())
+    }
+}
+
+impl TryFrom<String> for Color {
+    type Error = ThumbsdownError;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+impl From<Color> for String {
+    fn from(color: Color) -> Self {
+        color.to_string()
+    }
+}
+
+/// Built-in color schemes for the sheet.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Theme {
+    /// Black text on white
+    #[default]
+    Light,
+    /// Light text on near-black
+    Dark,
+    /// Black text on a transparent background, for PNG, WebP or AVIF output
+    Transparent,
+}
+
+impl Theme {
+    pub fn style(self) -> Style {
+        match self {
+            Theme::Light => Style::default(),
+            Theme::Dark => {
+                let background = Color::rgb(30, 30, 30);
+                Style {
+                    background,
+                    border_color: background,
+                    header_text: Color::rgb(230, 230, 230),
+                    header_background: background,
+                    ..Style::default()
+                }
+            }
+            Theme::Transparent => Style {
+                background: Color::TRANSPARENT,
+                border_color: Color::TRANSPARENT,
+                header_background: Color::TRANSPARENT,
+                ..Style::default()
+            },
+        }
+    }
+}
+
+/// Colors and spacing of a rendered sheet. All sizes are in pixels of the
+/// final image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Style {
+    /// Fill behind the tiles, in the gutters and margins
+    pub background: Color,
+    pub border_color: Color,
+    /// Border drawn around each thumbnail, inside its width
+    pub border_width: u32,
+    /// Space between neighbouring tiles
+    pub gutter: u32,
+    /// Space around the header and grid
+    pub margin: u32,
+    pub header_text: Color,
+    /// Fill of the header band across the sheet
+    pub header_background: Color,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Self {
+            background: Color::WHITE,
+            border_color: Color::WHITE,
+            border_width: DEFAULT_BORDER_WIDTH,
+            gutter: 0,
+            margin: 0,
+            header_text: Color::BLACK,
+            header_background: Color::WHITE,
+        }
+    }
+}
+
+impl Style {
+    /// The style without borders, gutters or margins, for sprites whose tile
+    /// positions are computed from the thumbnail size alone.
+    pub fn packed(self) -> Self {
+        Self {
+            border_width: 0,
+            gutter: 0,
+            margin: 0,
+            ..self
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_hex_and_named_colors() {
+        assert_eq!(
+            "#ff8000".parse::<Color>().expect("parse"),
+            Color::rgb(255, 128, 0)
+        );
+        assert_eq!(
+            "1E1E1E".parse::<Color>().expect("parse"),
+            Color::rgb(30, 30, 30)
+        );
+        assert_eq!(
+            "#f80".parse::<Color>().expect("parse"),
+            Color::rgb(255, 136, 0)
+        );
+        assert_eq!(
+            "#ff800080".parse::<Color>().expect("parse"),
+            Color([255, 128, 0, 128])
+        );
+        assert_eq!("White".parse::<Color>().expect("parse"), Color::WHITE);
+        assert_eq!(
+            "transparent".parse::<Color>().expect("parse"),
+            Color::TRANSPARENT
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_colors() {
+        for input in [
+            "", "#", "#12", "#12345", "#1234567", "#gggggg", "blurple", "#+12345",
+        ] {
+            assert!(
+                matches!(
+                    input.parse::<Color>(),
+                    Err(ThumbsdownError::InvalidColor(_))
+                ),
+                "{input}"
+            );
+        }
+    }
+
+    #[test]
+    fn display_round_trips() {
+        let color = Color::rgb(1, 171, 255);
+        assert_eq!(color.to_string(), "#01abff");
+        assert_eq!(color.to_string().parse::<Color>().expect("parse"), color);
+        let translucent = Color([1, 171, 255, 16]);
+        assert_eq!(translucent.to_string(), "#01abff10");
+        assert_eq!(
+            translucent.to_string().parse::<Color>().expect("parse"),
+            translucent
+        );
+    }
+
+    #[test]
+    fn dark_theme_keeps_spacing_of_light() {
+        let (light, dark) = (Theme::Light.style(), Theme::Dark.style());
+        assert_eq!(light, Style::default());
+        assert_ne!(dark.background, light.background);
+        assert_eq!(dark.border_width, light.border_width);
+        assert!(dark.background.is_opaque());
+        assert!(!Theme::Transparent.style().background.is_opaque());
+    }
+
+    #[test]
+    fn packed_drops_spacing_only() {
+        let style = Style {
+            gutter: 4,
+            margin: 8,
+            ..Theme::Dark.style()
+        }
+        .packed();
+        assert_eq!((style.border_width, style.gutter, style.margin), (0, 0, 0));
+        assert_eq!(style.background, Theme::Dark.style().background);
+    }
+}
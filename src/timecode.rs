@@ -0,0 +1,361 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::error::{Result, ThumbsdownError};
+
+/// A position in a video, resolved against its duration once it is known.
+///
+/// Parsed from plain seconds (`90.5`), clock time (`1:30`, `01:02:03.250`),
+/// a percentage of the duration (`5%`) or an offset from the end (`-2:00`).
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "RawTimeSpec", into = "String")]
+pub enum TimeSpec {
+    /// Seconds from the start
+    Seconds(f64),
+    /// Percentage of the duration
+    Percent(f64),
+    /// Seconds before the end
+    FromEnd(f64),
+}
+
+impl TimeSpec {
+    /// The time in seconds for a video of `duration` seconds.
+    pub fn resolve(&self, duration: f64) -> Result<f64> {
+        let time = match *self {
+            TimeSpec::Seconds(secs) => secs,
+            TimeSpec::Percent(percent) => duration * percent / 100.0,
+            TimeSpec::FromEnd(secs) => duration - secs,
+        };
+        if !(0.0..=duration).contains(&time) {
+            return Err(ThumbsdownError::TimeOutOfRange {
+                time: self.to_string(),
+                duration,
+            });
+        }
+        Ok(time)
+    }
+}
+
+impl From<f64> for TimeSpec {
+    fn from(secs: f64) -> Self {
+        if secs < 0.0 {
+            TimeSpec::FromEnd(-secs)
+        } else {
+            TimeSpec::Seconds(secs)
+        }
+    }
+}
+
+impl FromStr for TimeSpec {
+    type Err = ThumbsdownError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || ThumbsdownError::InvalidTime(s.to_string());
+        let s = s.trim();
+        let (from_end, body) = match s.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s),
+        };
+
+        if let Some(percent) = body.strip_suffix('%') {
+            if from_end {
+                return Err(invalid());
+            }
+            return parse_decimal(percent)
+                .map(TimeSpec::Percent)
+                .ok_or_else(invalid);
+        }
+
+        let secs = parse_clock(body).ok_or_else(invalid)?;
+        if from_end {
+            Ok(TimeSpec::FromEnd(secs))
+        } else {
+            Ok(TimeSpec::Seconds(secs))
+        }
+    }
+}
+
+impl fmt::Display for TimeSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimeSpec::Seconds(secs) => write!(f, "{secs}"),
+            TimeSpec::Percent(percent) => write!(f, "{percent}%"),
+            TimeSpec::FromEnd(secs) => write!(f, "-{secs}"),
+        }
+    }
+}
+
+impl From<TimeSpec> for String {
+    fn from(spec: TimeSpec) -> Self {
+        spec.to_string()
+    }
+}
+
+/// Config files may give times as numbers or strings.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawTimeSpec {
+    Seconds(f64),
+    Text(String),
+}
+
+impl TryFrom<RawTimeSpec> for TimeSpec {
+    type Error = ThumbsdownError;
+
+    fn try_from(raw: RawTimeSpec) -> Result<Self> {
+        match raw {
+            RawTimeSpec::Seconds(secs) => Ok(secs.into()),
+            RawTimeSpec::Text(text) => text.parse(),
+        }
+    }
+}
+
+/// Parses a positive interval such as `30s`, `2m`, `1h30m`, `1:30` or `45`
+/// into seconds.
+pub fn parse_interval(s: &str) -> Result<f64> {
+    let invalid = || ThumbsdownError::InvalidInterval(s.to_string());
+    let text = s.trim();
+    let secs = if text.ends_with(|c: char| c.is_ascii_alphabetic()) {
+        parse_units(text)
+    } else {
+        parse_clock(text)
+    }
+    .ok_or_else(invalid)?;
+    if secs <= 0.0 {
+        return Err(invalid());
+    }
+    Ok(secs)
+}
+
+/// Deserializes an interval given as seconds or as text `parse_interval`
+/// accepts, for config files.
+pub fn deserialize_interval<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<f64, D::Error> {
+    match RawTimeSpec::deserialize(deserializer)? {
+        RawTimeSpec::Seconds(secs) => Ok(secs),
+        RawTimeSpec::Text(text) => parse_interval(&text).map_err(D::Error::custom),
+    }
+}
+
+/// `deserialize_interval` for an optional interval.
+pub fn deserialize_optional_interval<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Option<f64>, D::Error> {
+    match Option::<RawTimeSpec>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(RawTimeSpec::Seconds(secs)) => Ok(Some(secs)),
+        Some(RawTimeSpec::Text(text)) => parse_interval(&text).map(Some).map_err(D::Error::custom),
+    }
+}
+
+/// A sequence of numbers with `h`, `m` or `s` units, e.g. `1h30m`.
+fn parse_units(s: &str) -> Option<f64> {
+    let mut total = 0.0;
+    let mut rest = s;
+    while !rest.is_empty() {
+        let unit_at = rest.find(|c: char| !(c.is_ascii_digit() || c == '.'))?;
+        let value = parse_decimal(&rest[..unit_at])?;
+        let scale = match rest.as_bytes()[unit_at] {
+            b'h' => 3600.0,
+            b'm' => 60.0,
+            b's' => 1.0,
+            _ => return None,
+        };
+        total += value * scale;
+        rest = &rest[unit_at + 1..];
+    }
+    Some(total)
+}
+
+/// `SS[.mmm]`, `MM:SS[.mmm]` or `HH:MM:SS[.mmm]`. Minutes and seconds must
+/// be below 60 when a larger unit precedes them.
+fn parse_clock(s: &str) -> Option<f64> {
+    let parts: Vec<&str> = s.split(':').collect();
+    if parts.len() > 3 {
+        return None;
+    }
+    let (secs, units) = parts.split_last()?;
+    let secs = parse_decimal(secs)?;
+    if !units.is_empty() && secs >= 60.0 {
+        return None;
+    }
+
+    let mut total = 0u64;
+    for (i, unit) in units.iter().enumerate() {
+        if unit.is_empty() || !unit.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let value: u64 = unit.parse().ok()?;
+        if i > 0 && value >= 60 {
+            return None;
+        }
+        total = total * 60 + value;
+    }
+    Some(total as f64 * 60.0 + secs)
+}
+
+/// A non-negative decimal without sign or exponent.
+fn parse_decimal(s: &str) -> Option<f64> {
+    if !s.bytes().all(|b| b.is_ascii_digit() || b == b'.') {
+        return None;
+    }
+    s.parse().ok()
+}
+
+/// Formats seconds as `HH:MM:SS`, truncating fractional seconds.
+pub fn format_hms(secs: f64) -> String {
+    let total = secs.max(0.0) as u64;
+    format!(
+        "{:02}:{:02}:{:02}",
+        total / 3600,
+        (total % 3600) / 60,
+        total % 60
+    )
+}
+
+/// Formats seconds as a WebVTT timestamp, `HH:MM:SS.mmm`.
+pub fn format_vtt(secs: f64) -> String {
+    let ms = (secs.max(0.0) * 1000.0).round() as u64;
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        ms / 3_600_000,
+        (ms / 60_000) % 60,
+        (ms / 1000) % 60,
+        ms % 1000
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_hms_pads_fields() {
+        assert_eq!(format_hms(0.0), "00:00:00");
+        assert_eq!(format_hms(61.9), "00:01:01");
+        assert_eq!(format_hms(3725.0), "01:02:05");
+    }
+
+    #[test]
+    fn format_hms_clamps_negative() {
+        assert_eq!(format_hms(-3.0), "00:00:00");
+    }
+
+    #[test]
+    fn format_vtt_keeps_milliseconds() {
+        assert_eq!(format_vtt(0.0), "00:00:00.000");
+        assert_eq!(format_vtt(3723.25), "01:02:03.250");
+        assert_eq!(format_vtt(59.9996), "00:01:00.000");
+    }
+
+    #[test]
+    fn parses_seconds_and_clock_times() {
+        assert_eq!(
+            "90".parse::<TimeSpec>().expect("parse"),
+            TimeSpec::Seconds(90.0)
+        );
+        assert_eq!(
+            "2.5".parse::<TimeSpec>().expect("parse"),
+            TimeSpec::Seconds(2.5)
+        );
+        assert_eq!(
+            "1:30".parse::<TimeSpec>().expect("parse"),
+            TimeSpec::Seconds(90.0)
+        );
+        assert_eq!(
+            "01:02:03.250".parse::<TimeSpec>().expect("parse"),
+            TimeSpec::Seconds(3723.25)
+        );
+    }
+
+    #[test]
+    fn parses_percentages_and_offsets_from_end() {
+        assert_eq!(
+            "5%".parse::<TimeSpec>().expect("parse"),
+            TimeSpec::Percent(5.0)
+        );
+        assert_eq!(
+            "-2:00".parse::<TimeSpec>().expect("parse"),
+            TimeSpec::FromEnd(120.0)
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_times() {
+        for input in [
+            "", "abc", "1:60", "1:75:00", "1:2:3:4", "1e3", "-5%", "::", "+5",
+        ] {
+            assert!(
+                matches!(
+                    input.parse::<TimeSpec>(),
+                    Err(ThumbsdownError::InvalidTime(_))
+                ),
+                "{input}"
+            );
+        }
+    }
+
+    #[test]
+    fn resolves_against_duration() {
+        assert_eq!(
+            TimeSpec::Seconds(30.0).resolve(100.0).expect("resolve"),
+            30.0
+        );
+        assert_eq!(
+            TimeSpec::Percent(5.0).resolve(200.0).expect("resolve"),
+            10.0
+        );
+        assert_eq!(
+            TimeSpec::FromEnd(120.0).resolve(600.0).expect("resolve"),
+            480.0
+        );
+    }
+
+    #[test]
+    fn resolve_rejects_times_outside_video() {
+        for spec in [
+            TimeSpec::Seconds(101.0),
+            TimeSpec::Percent(150.0),
+            TimeSpec::FromEnd(200.0),
+        ] {
+            assert!(matches!(
+                spec.resolve(100.0),
+                Err(ThumbsdownError::TimeOutOfRange { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn parses_intervals() {
+        assert_eq!(parse_interval("30s").expect("parse"), 30.0);
+        assert_eq!(parse_interval("2m").expect("parse"), 120.0);
+        assert_eq!(parse_interval("1h30m").expect("parse"), 5400.0);
+        assert_eq!(parse_interval("1:30").expect("parse"), 90.0);
+        assert_eq!(parse_interval("0.5").expect("parse"), 0.5);
+    }
+
+    #[test]
+    fn rejects_bad_intervals() {
+        for input in ["", "0", "0s", "-5s", "5x", "s", "10ms", "1:99"] {
+            assert!(
+                matches!(
+                    parse_interval(input),
+                    Err(ThumbsdownError::InvalidInterval(_))
+                ),
+                "{input}"
+            );
+        }
+    }
+
+    #[test]
+    fn display_round_trips() {
+        for input in ["90.5", "5%", "-120"] {
+            let spec: TimeSpec = input.parse().expect("parse");
+            assert_eq!(spec.to_string(), input);
+        }
+    }
+}
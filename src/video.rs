@@ -1,18 +1,77 @@
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::ops::Range;
 use std::path::Path;
-use std::process::Command;
+use std::process::{Child, Command, ExitStatus};
 
-use serde::Deserialize;
+use clap::ValueEnum;
+use image::RgbImage;
+use serde::{Deserialize, Serialize};
 
 use crate::error::{Result, ThumbsdownError};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct VideoInfo {
     pub filename: String,
     pub duration: f64,
+    /// Stored frame width; see `display_size` for the size players show
     pub width: u32,
     pub height: u32,
+    /// Clockwise rotation in degrees (0, 90, 180 or 270) that players apply,
+    /// from the display matrix or `rotate` tag
+    pub rotation: u32,
+    /// Sample (pixel) aspect ratio as `(num, den)` when pixels are not square
+    pub sample_aspect_ratio: Option<(u32, u32)>,
     pub codec: String,
     pub fps: f64,
+    pub pix_fmt: Option<String>,
+    /// File size in bytes
+    pub size: Option<u64>,
+    /// Overall bitrate in bits per second
+    pub bitrate: Option<u64>,
+    pub container: Option<String>,
+    /// First audio stream, if any
+    pub audio: Option<AudioInfo>,
+}
+
+impl VideoInfo {
+    /// Size the video is shown at: the stored width stretched by the sample
+    /// aspect ratio, then turned by the rotation. Frames are captured at
+    /// this size.
+    pub fn display_size(&self) -> (u32, u32) {
+        let width = match self.sample_aspect_ratio {
+            Some((num, den)) => {
+                let (num, den) = (num as u64, den as u64);
+                ((self.width as u64 * num + den / 2) / den).max(1) as u32
+            }
+            None => self.width,
+        };
+        if self.rotation % 180 == 90 {
+            (self.height, width)
+        } else {
+            (width, self.height)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct AudioInfo {
+    pub codec: String,
+    pub channels: u32,
+    pub channel_layout: Option<String>,
+    /// Sample rate in Hz
+    pub sample_rate: Option<u32>,
+}
+
+/// How frames are pulled out of the video.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ExtractMode {
+    /// One ffmpeg process per thumbnail, seeking directly to each timestamp
+    #[default]
+    PerFrame,
+    /// A single ffmpeg process decoding the video once and selecting all frames
+    SinglePass,
 }
 
 #[derive(Deserialize)]
@@ -25,6 +84,10 @@ struct FfprobeOutput {
 struct FfprobeFormat {
     filename: Option<String>,
     duration: Option<String>,
+    size: Option<String>,
+    bit_rate: Option<String>,
+    format_name: Option<String>,
+    format_long_name: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -35,6 +98,63 @@ struct FfprobeStream {
     height: Option<u32>,
     r_frame_rate: Option<String>,
     duration: Option<String>,
+    pix_fmt: Option<String>,
+    sample_aspect_ratio: Option<String>,
+    display_aspect_ratio: Option<String>,
+    tags: Option<HashMap<String, String>>,
+    side_data_list: Option<Vec<FfprobeSideData>>,
+    channels: Option<u32>,
+    channel_layout: Option<String>,
+    sample_rate: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct FfprobeSideData {
+    side_data_type: Option<String>,
+    /// Counter-clockwise degrees, as ffprobe reports the display matrix
+    rotation: Option<f64>,
+}
+
+impl FfprobeStream {
+    /// Clockwise rotation from the display matrix, or failing that the
+    /// legacy `rotate` tag, snapped to a quarter turn.
+    fn rotation(&self) -> u32 {
+        let matrix = self
+            .side_data_list
+            .iter()
+            .flatten()
+            .filter(|data| data.side_data_type.as_deref() == Some("Display Matrix"))
+            .find_map(|data| data.rotation)
+            .map(|degrees| -degrees);
+        let tag = || {
+            self.tags
+                .as_ref()?
+                .get("rotate")
+                .and_then(|degrees| degrees.trim().parse::<f64>().ok())
+        };
+        let degrees = matrix.or_else(tag).unwrap_or(0.0);
+        ((degrees / 90.0).round() as i64).rem_euclid(4) as u32 * 90
+    }
+
+    /// Sample aspect ratio when it is known and not square. A missing or
+    /// unknown (`0:1`) one is worked out from the display aspect ratio.
+    fn sample_aspect_ratio(&self) -> Option<(u32, u32)> {
+        let sar = match self.sample_aspect_ratio.as_deref().and_then(parse_ratio) {
+            Some(sar) => sar,
+            None => {
+                let (dar_num, dar_den) =
+                    self.display_aspect_ratio.as_deref().and_then(parse_ratio)?;
+                let (width, height) = (self.width? as u64, self.height? as u64);
+                let (num, den) = (dar_num as u64 * height, dar_den as u64 * width);
+                let divisor = gcd(num, den);
+                (
+                    u32::try_from(num / divisor).ok()?,
+                    u32::try_from(den / divisor).ok()?,
+                )
+            }
+        };
+        (sar.0 != sar.1).then_some(sar)
+    }
 }
 
 pub fn check_dependencies() -> Result<()> {
@@ -121,41 +241,379 @@ pub fn probe(path: &Path) -> Result<VideoInfo> {
         .map(parse_frame_rate)
         .unwrap_or(0.0);
 
+    let audio = data
+        .streams
+        .iter()
+        .find(|s| s.codec_type.as_deref() == Some("audio"))
+        .map(|s| AudioInfo {
+            codec: s
+                .codec_name
+                .clone()
+                .unwrap_or_else(|| "unknown".to_string()),
+            channels: s.channels.unwrap_or(0),
+            channel_layout: s.channel_layout.clone(),
+            sample_rate: s.sample_rate.as_deref().and_then(|r| r.parse().ok()),
+        });
+
     Ok(VideoInfo {
         filename,
         duration,
         width: video_stream.width.unwrap_or(0),
         height: video_stream.height.unwrap_or(0),
+        rotation: video_stream.rotation(),
+        sample_aspect_ratio: video_stream.sample_aspect_ratio(),
         codec: video_stream
             .codec_name
             .clone()
             .unwrap_or_else(|| "unknown".to_string()),
         fps,
+        pix_fmt: video_stream.pix_fmt.clone(),
+        size: data.format.size.as_deref().and_then(|s| s.parse().ok()),
+        bitrate: data.format.bit_rate.as_deref().and_then(|b| b.parse().ok()),
+        container: data.format.format_long_name.or(data.format.format_name),
+        audio,
     })
 }
 
-pub fn capture_frame(video_path: &Path, time_secs: f64, output_path: &Path) -> Result<()> {
-    let status = Command::new("ffmpeg")
-        .args(["-y", "-ss"])
+/// Captures a single frame at `time_secs`, decoded straight into memory as
+/// rgb24 at `width`x`height`.
+pub fn capture_frame(
+    video_path: &Path,
+    time_secs: f64,
+    width: u32,
+    height: u32,
+) -> Result<RgbImage> {
+    let output = Command::new("ffmpeg")
+        .arg("-ss")
         .arg(format!("{time_secs:.3}"))
         .arg("-i")
         .arg(video_path)
-        .args(["-frames:v", "1", "-q:v", "2"])
-        .arg(output_path)
-        .stdout(std::process::Stdio::null())
-        .stderr(std::process::Stdio::null())
-        .status()?;
+        .args(["-frames:v", "1"])
+        .args(rawvideo_args(width, height))
+        .stdin(std::process::Stdio::null())
+        .output()?;
 
-    if !status.success() {
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
         return Err(ThumbsdownError::FrameCaptureFailed {
             time: time_secs,
-            reason: format!("ffmpeg exited with {status}"),
+            reason: stderr
+                .lines()
+                .last()
+                .map(str::to_string)
+                .unwrap_or_else(|| format!("ffmpeg exited with {}", output.status)),
+        });
+    }
+
+    let len = output.stdout.len();
+    RgbImage::from_raw(width, height, output.stdout).ok_or_else(|| {
+        ThumbsdownError::FrameCaptureFailed {
+            time: time_secs,
+            reason: format!(
+                "expected {} bytes of rgb24, got {len}",
+                frame_size(width, height)
+            ),
+        }
+    })
+}
+
+/// Captures up to `count` consecutive frames starting at `time_secs`, as
+/// rgb24 at `width`x`height`. Fewer frames are returned near the end of the
+/// video.
+pub fn capture_burst(
+    video_path: &Path,
+    time_secs: f64,
+    count: u32,
+    width: u32,
+    height: u32,
+) -> Result<Vec<RgbImage>> {
+    let output = Command::new("ffmpeg")
+        .arg("-ss")
+        .arg(format!("{time_secs:.3}"))
+        .arg("-i")
+        .arg(video_path)
+        .args(["-frames:v", &count.to_string()])
+        .args(rawvideo_args(width, height))
+        .stdin(std::process::Stdio::null())
+        .output()?;
+
+    let frames: Vec<RgbImage> = output
+        .stdout
+        .chunks_exact(frame_size(width, height))
+        .filter_map(|buf| RgbImage::from_raw(width, height, buf.to_vec()))
+        .collect();
+    if frames.is_empty() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(ThumbsdownError::FrameCaptureFailed {
+            time: time_secs,
+            reason: stderr
+                .lines()
+                .last()
+                .map(str::to_string)
+                .unwrap_or_else(|| format!("ffmpeg exited with {}", output.status)),
+        });
+    }
+    Ok(frames)
+}
+
+/// Captures the first frame at or after each of the ascending timestamps in
+/// a single ffmpeg run. Frames are streamed as rgb24 at `width`x`height` and
+/// handed to `on_frame` in timestamp order as soon as they are decoded.
+/// Timestamps that fall on the same frame of a `fps` video each get a copy
+/// of it.
+pub fn capture_frames(
+    video_path: &Path,
+    timestamps: &[f64],
+    fps: f64,
+    width: u32,
+    height: u32,
+    mut on_frame: impl FnMut(usize, RgbImage) -> Result<()>,
+) -> Result<()> {
+    if timestamps.is_empty() {
+        return Ok(());
+    }
+
+    // One frame satisfies every select term it crosses, so such timestamps
+    // are requested once
+    let runs = frame_runs(timestamps, fps);
+    let firsts: Vec<f64> = runs.iter().map(|run| timestamps[run.start]).collect();
+    // The expression grows with every timestamp and would outgrow the limit
+    // on a single argument, so ffmpeg reads the graph from a file
+    let mut script = tempfile::NamedTempFile::new()?;
+    write!(script, "select='{}'", select_expr(&firsts))?;
+    script.flush()?;
+
+    let child = Command::new("ffmpeg")
+        .arg("-i")
+        .arg(video_path)
+        .arg("-filter_script:v")
+        .arg(script.path())
+        // -fps_mode only exists from ffmpeg 5.1; newer versions still accept
+        // the deprecated -vsync
+        .args(["-vsync", "passthrough"])
+        .args(rawvideo_args(width, height))
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::null())
+        .spawn()?;
+
+    let (captured, status) = stream_frames(child, runs.len(), width, height, |run, frame| {
+        let run = runs[run].clone();
+        let copies = std::iter::repeat_n(frame, run.len());
+        run.zip(copies)
+            .try_for_each(|(i, frame)| on_frame(i, frame))
+    })?;
+
+    if captured < runs.len() {
+        return Err(ThumbsdownError::FrameCaptureFailed {
+            time: firsts[captured],
+            reason: if status.success() {
+                format!(
+                    "single-pass extraction produced {captured} of {} frames",
+                    runs.len()
+                )
+            } else {
+                format!("ffmpeg exited with {status}")
+            },
         });
     }
 
     Ok(())
 }
 
+/// Captures up to `count` frames `interval` seconds apart from the start of
+/// the video in a single ffmpeg run, using its `fps` filter. Frames are
+/// streamed as rgb24 at `width`x`height` and handed to `on_frame` in order.
+/// Returns the number of frames captured, which may fall short of `count`
+/// near the end of the video.
+pub fn capture_every(
+    video_path: &Path,
+    interval: f64,
+    count: usize,
+    width: u32,
+    height: u32,
+    on_frame: impl FnMut(usize, RgbImage) -> Result<()>,
+) -> Result<usize> {
+    if count == 0 {
+        return Ok(0);
+    }
+
+    let child = Command::new("ffmpeg")
+        .arg("-i")
+        .arg(video_path)
+        .arg("-vf")
+        .arg(format!("fps=1/{interval}"))
+        .args(["-frames:v", &count.to_string()])
+        .args(rawvideo_args(width, height))
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::null())
+        .spawn()?;
+
+    let (captured, status) = stream_frames(child, count, width, height, on_frame)?;
+    if captured == 0 || (captured < count && !status.success()) {
+        return Err(ThumbsdownError::FrameCaptureFailed {
+            time: captured as f64 * interval,
+            reason: if status.success() {
+                "ffmpeg produced no frames".to_string()
+            } else {
+                format!("ffmpeg exited with {status}")
+            },
+        });
+    }
+
+    Ok(captured)
+}
+
+/// Reads rgb24 frames of `width`x`height` from the piped stdout of `child`
+/// and hands each to `on_frame` until `count` were read or the stream ends.
+/// Returns the number of frames read and ffmpeg's exit status.
+fn stream_frames(
+    mut child: Child,
+    count: usize,
+    width: u32,
+    height: u32,
+    mut on_frame: impl FnMut(usize, RgbImage) -> Result<()>,
+) -> Result<(usize, ExitStatus)> {
+    let mut stdout = child.stdout.take().expect("stdout is piped");
+    let mut read = 0;
+    let mut result = Ok(());
+    while read < count {
+        let mut buf = vec![0; frame_size(width, height)];
+        if let Err(e) = stdout.read_exact(&mut buf) {
+            if e.kind() != io::ErrorKind::UnexpectedEof {
+                result = Err(e.into());
+            }
+            break;
+        }
+        let frame = RgbImage::from_raw(width, height, buf).expect("buffer matches frame size");
+        if let Err(e) = on_frame(read, frame) {
+            result = Err(e);
+            break;
+        }
+        read += 1;
+    }
+    drop(stdout);
+
+    let status = child.wait()?;
+    result?;
+    Ok((read, status))
+}
+
+/// Runs ffmpeg's scene detection over the whole video and returns the
+/// `(time, score)` of every frame whose scene-change score is above zero.
+pub fn scene_scores(video_path: &Path) -> Result<Vec<(f64, f64)>> {
+    let output = Command::new("ffmpeg")
+        .arg("-i")
+        .arg(video_path)
+        .args([
+            "-an",
+            "-vf",
+            "scale=160:-2,select='gt(scene,0)',metadata=print:file=-",
+            "-f",
+            "null",
+            "-",
+        ])
+        .stdin(std::process::Stdio::null())
+        .output()?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(ThumbsdownError::SceneDetectionFailed(
+            stderr
+                .lines()
+                .last()
+                .map(str::to_string)
+                .unwrap_or_else(|| format!("ffmpeg exited with {}", output.status)),
+        ));
+    }
+
+    Ok(parse_scene_scores(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Parses `metadata=print` output, pairing each `lavfi.scene_score` with the
+/// `pts_time` of the frame line preceding it.
+fn parse_scene_scores(output: &str) -> Vec<(f64, f64)> {
+    let mut scores = Vec::new();
+    let mut time = None;
+    for line in output.lines() {
+        let line = line.trim();
+        if line.starts_with("frame:") {
+            time = line
+                .split_whitespace()
+                .find_map(|field| field.strip_prefix("pts_time:"))
+                .and_then(|t| t.parse::<f64>().ok());
+        } else if let Some(score) = line.strip_prefix("lavfi.scene_score=") {
+            if let (Some(t), Ok(score)) = (time, score.parse::<f64>()) {
+                scores.push((t, score));
+            }
+        }
+    }
+    scores
+}
+
+/// Output options that stream rgb24 frames of `width`x`height` to stdout.
+fn rawvideo_args(width: u32, height: u32) -> [String; 7] {
+    [
+        "-s".to_string(),
+        format!("{width}x{height}"),
+        "-f".to_string(),
+        "rawvideo".to_string(),
+        "-pix_fmt".to_string(),
+        "rgb24".to_string(),
+        "pipe:1".to_string(),
+    ]
+}
+
+fn frame_size(width: u32, height: u32) -> usize {
+    width as usize * height as usize * 3
+}
+
+/// Builds a `select` filter expression that picks the first frame at or after
+/// each timestamp, i.e. a frame is kept when it has crossed a timestamp that
+/// the previously selected frame had not yet reached.
+fn select_expr(timestamps: &[f64]) -> String {
+    let terms: Vec<String> = timestamps
+        .iter()
+        .map(|t| format!("gte(t,{t:.3})*(isnan(prev_selected_t)+lt(prev_selected_t,{t:.3}))"))
+        .collect();
+    format!("gt({},0)", terms.join("+"))
+}
+
+/// Splits ascending `timestamps` into runs that select the same frame of a
+/// `fps` video, the first frame at or after them. Without a frame rate each
+/// timestamp is a run of its own.
+fn frame_runs(timestamps: &[f64], fps: f64) -> Vec<Range<usize>> {
+    // Frame index the select filter picks, at the millisecond precision of
+    // `select_expr`
+    let frame = |t: f64| ((t * 1000.0).round() / 1000.0 * fps - 1e-6).ceil();
+    let mut runs: Vec<Range<usize>> = Vec::new();
+    for (i, &time) in timestamps.iter().enumerate() {
+        match runs.last_mut() {
+            Some(run) if fps > 0.0 && frame(timestamps[run.start]) == frame(time) => {
+                run.end = i + 1;
+            }
+            _ => runs.push(i..i + 1),
+        }
+    }
+    runs
+}
+
+/// Parses an ffprobe `num:den` ratio; `None` when either side is 0.
+fn parse_ratio(ratio: &str) -> Option<(u32, u32)> {
+    let (num, den) = ratio.split_once(':')?;
+    let (num, den) = (num.trim().parse().ok()?, den.trim().parse().ok()?);
+    (num > 0 && den > 0).then_some((num, den))
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 {
+        a.max(1)
+    } else {
+        gcd(b, a % b)
+    }
+}
+
 fn parse_frame_rate(rate: &str) -> f64 {
     if let Some((num, den)) = rate.split_once('/') {
         let n: f64 = num.parse().unwrap_or(0.0);
@@ -198,6 +656,75 @@ mod tests {
         assert!((parse_frame_rate("abc") - 0.0).abs() < 0.01);
     }
 
+    #[test]
+    fn select_expr_has_term_per_timestamp() {
+        let expr = select_expr(&[1.0, 12.5]);
+        assert!(expr.starts_with("gt("));
+        assert!(expr.contains("gte(t,1.000)"));
+        assert!(expr.contains("lt(prev_selected_t,12.500)"));
+        assert_eq!(expr.matches("gte(t,").count(), 2);
+    }
+
+    #[test]
+    fn frame_runs_merge_timestamps_between_frames() {
+        // At 24 fps frame 240 is at 10.0s and frame 241 at 10.0417s
+        let timestamps = [10.0, 10.01, 10.02, 10.05, 20.0];
+        assert_eq!(frame_runs(&timestamps, 24.0), vec![0..1, 1..3, 3..4, 4..5]);
+        assert_eq!(
+            frame_runs(&timestamps, 0.0),
+            vec![0..1, 1..2, 2..3, 3..4, 4..5]
+        );
+    }
+
+    #[test]
+    fn capture_frames_empty_timestamps_is_noop() {
+        let mut calls = 0;
+        capture_frames(Path::new("video.mp4"), &[], 25.0, 16, 9, |_, _| {
+            calls += 1;
+            Ok(())
+        })
+        .expect("capture");
+        assert_eq!(calls, 0);
+    }
+
+    #[test]
+    fn capture_every_without_frames_is_noop() {
+        let count =
+            capture_every(Path::new("video.mp4"), 10.0, 0, 16, 9, |_, _| Ok(())).expect("capture");
+        assert_eq!(count, 0);
+    }
+
+    #[test]
+    fn parse_scene_scores_pairs_time_and_score() {
+        let output = "frame:0    pts:1001   pts_time:1.001\n\
+                      lavfi.scene_score=0.456000\n\
+                      frame:1    pts:5005   pts_time:5.005\n\
+                      lavfi.scene_score=0.912000\n";
+        let scores = parse_scene_scores(output);
+        assert_eq!(scores.len(), 2);
+        assert!((scores[0].0 - 1.001).abs() < 1e-9);
+        assert!((scores[0].1 - 0.456).abs() < 1e-9);
+        assert!((scores[1].0 - 5.005).abs() < 1e-9);
+    }
+
+    #[test]
+    fn parse_scene_scores_ignores_garbage() {
+        assert!(parse_scene_scores("lavfi.scene_score=0.5\nnoise\n").is_empty());
+    }
+
+    #[test]
+    fn frame_size_is_rgb24() {
+        assert_eq!(frame_size(1920, 1080), 1920 * 1080 * 3);
+    }
+
+    #[test]
+    fn rawvideo_args_request_exact_size() {
+        let args = rawvideo_args(640, 360);
+        assert_eq!(args[1], "640x360");
+        assert!(args.contains(&"rgb24".to_string()));
+        assert_eq!(args.last().map(String::as_str), Some("pipe:1"));
+    }
+
     #[test]
     fn probe_nonexistent_file_returns_error() {
         let result = probe(Path::new("/nonexistent_video_xyz.mp4"));
@@ -240,4 +767,118 @@ mod tests {
             .unwrap_or(0.0);
         assert!((dur - 120.5).abs() < 0.01);
     }
+
+    #[test]
+    fn ffprobe_json_parsing_audio_and_format() {
+        let json = r#"{
+            "streams": [{
+                "codec_type": "video",
+                "codec_name": "h264",
+                "pix_fmt": "yuv420p"
+            }, {
+                "codec_type": "audio",
+                "codec_name": "aac",
+                "channels": 2,
+                "channel_layout": "stereo",
+                "sample_rate": "48000"
+            }],
+            "format": {
+                "filename": "/path/to/video.mp4",
+                "size": "10485760",
+                "bit_rate": "4500000",
+                "format_name": "mov,mp4,m4a,3gp,3g2,mj2",
+                "format_long_name": "QuickTime / MOV"
+            }
+        }"#;
+
+        let data: FfprobeOutput = serde_json::from_str(json).expect("parse");
+        let audio = data
+            .streams
+            .iter()
+            .find(|s| s.codec_type.as_deref() == Some("audio"))
+            .expect("audio stream");
+
+        assert_eq!(audio.codec_name.as_deref(), Some("aac"));
+        assert_eq!(audio.channels, Some(2));
+        assert_eq!(audio.sample_rate.as_deref(), Some("48000"));
+        assert_eq!(data.streams[0].pix_fmt.as_deref(), Some("yuv420p"));
+        assert_eq!(data.format.size.as_deref(), Some("10485760"));
+        assert_eq!(data.format.bit_rate.as_deref(), Some("4500000"));
+        assert_eq!(
+            data.format.format_long_name.as_deref(),
+            Some("QuickTime / MOV")
+        );
+    }
+
+    fn video_stream(json: &str) -> FfprobeStream {
+        serde_json::from_str(json).expect("parse")
+    }
+
+    #[test]
+    fn rotation_prefers_display_matrix_over_tag() {
+        let stream = video_stream(
+            r#"{
+                "width": 1920,
+                "height": 1080,
+                "tags": {"rotate": "180"},
+                "side_data_list": [{
+                    "side_data_type": "Display Matrix",
+                    "displaymatrix": "...",
+                    "rotation": -90
+                }]
+            }"#,
+        );
+        assert_eq!(stream.rotation(), 90);
+        assert_eq!(
+            video_stream(r#"{"tags": {"rotate": "270"}}"#).rotation(),
+            270
+        );
+        assert_eq!(
+            video_stream(
+                r#"{"side_data_list": [{"side_data_type": "Display Matrix", "rotation": 90.0}]}"#
+            )
+            .rotation(),
+            270
+        );
+        assert_eq!(video_stream("{}").rotation(), 0);
+    }
+
+    #[test]
+    fn sample_aspect_ratio_from_sar_or_dar() {
+        let dvd = video_stream(
+            r#"{"width": 720, "height": 480, "sample_aspect_ratio": "32:27", "display_aspect_ratio": "16:9"}"#,
+        );
+        assert_eq!(dvd.sample_aspect_ratio(), Some((32, 27)));
+        let unknown_sar = video_stream(
+            r#"{"width": 720, "height": 576, "sample_aspect_ratio": "0:1", "display_aspect_ratio": "4:3"}"#,
+        );
+        assert_eq!(unknown_sar.sample_aspect_ratio(), Some((16, 15)));
+        let square = video_stream(
+            r#"{"width": 1920, "height": 1080, "sample_aspect_ratio": "1:1", "display_aspect_ratio": "16:9"}"#,
+        );
+        assert_eq!(square.sample_aspect_ratio(), None);
+    }
+
+    #[test]
+    fn display_size_applies_sar_and_rotation() {
+        let dvd = VideoInfo {
+            width: 720,
+            height: 480,
+            sample_aspect_ratio: Some((32, 27)),
+            ..Default::default()
+        };
+        assert_eq!(dvd.display_size(), (853, 480));
+        let phone = VideoInfo {
+            width: 1920,
+            height: 1080,
+            rotation: 90,
+            ..Default::default()
+        };
+        assert_eq!(phone.display_size(), (1080, 1920));
+        let flipped = VideoInfo {
+            rotation: 180,
+            ..phone
+        };
+        assert_eq!(flipped.display_size(), (1920, 1080));
+    }
 }
@@ -0,0 +1,109 @@
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+use crate::timecode;
+
+/// A WebVTT cue pointing at one tile of a sprite image.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cue {
+    pub start: f64,
+    pub end: f64,
+    /// Sprite URL relative to the cue file
+    pub url: String,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// `sprite.jpg` -> `sprite.vtt`
+pub fn vtt_path(output: &Path) -> PathBuf {
+    output.with_extension("vtt")
+}
+
+/// The URL of a sprite written next to the cue file: its file name,
+/// percent-encoded.
+pub fn sprite_url(sprite: &Path) -> String {
+    let name = sprite.file_name().unwrap_or_default().to_string_lossy();
+    let mut url = String::with_capacity(name.len());
+    for byte in name.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                url.push(byte as char)
+            }
+            _ => {
+                let _ = write!(url, "%{byte:02X}");
+            }
+        }
+    }
+    url
+}
+
+pub fn render(cues: &[Cue]) -> String {
+    let mut out = String::from("WEBVTT\n");
+    for cue in cues {
+        let _ = write!(
+            out,
+            "\n{} --> {}\n{}#xywh={},{},{},{}\n",
+            timecode::format_vtt(cue.start),
+            timecode::format_vtt(cue.end),
+            cue.url,
+            cue.x,
+            cue.y,
+            cue.width,
+            cue.height
+        );
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_writes_header_and_cues() {
+        let cues = [
+            Cue {
+                start: 0.0,
+                end: 10.0,
+                url: "sprite.jpg".to_string(),
+                x: 0,
+                y: 0,
+                width: 160,
+                height: 90,
+            },
+            Cue {
+                start: 10.0,
+                end: 20.5,
+                url: "sprite.jpg".to_string(),
+                x: 160,
+                y: 0,
+                width: 160,
+                height: 90,
+            },
+        ];
+        assert_eq!(
+            render(&cues),
+            "WEBVTT\n\n\
+             00:00:00.000 --> 00:00:10.000\nsprite.jpg#xywh=0,0,160,90\n\n\
+             00:00:10.000 --> 00:00:20.500\nsprite.jpg#xywh=160,0,160,90\n"
+        );
+    }
+
+    #[test]
+    fn sprite_url_is_relative_and_escaped() {
+        assert_eq!(
+            sprite_url(Path::new("/srv/media/my clip-001.jpg")),
+            "my%20clip-001.jpg"
+        );
+    }
+
+    #[test]
+    fn vtt_path_replaces_extension() {
+        assert_eq!(
+            vtt_path(Path::new("out/sprite.jpg")),
+            PathBuf::from("out/sprite.vtt")
+        );
+    }
+}
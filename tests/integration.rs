@@ -1,6 +1,35 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
 use assert_cmd::cargo::cargo_bin_cmd;
 use predicates::prelude::*;
 
+/// Renders a 4 second `testsrc` clip into `dir`, or returns `None` when
+/// ffmpeg is not installed so the end-to-end tests can be skipped.
+fn test_clip(dir: &Path) -> Option<PathBuf> {
+    let available = |tool: &str| {
+        Command::new(tool)
+            .arg("-version")
+            .output()
+            .is_ok_and(|output| output.status.success())
+    };
+    if !available("ffmpeg") || !available("ffprobe") {
+        eprintln!("ffmpeg not found, skipping");
+        return None;
+    }
+
+    let clip = dir.join("testsrc.mp4");
+    let status = Command::new("ffmpeg")
+        .args(["-v", "error", "-f", "lavfi", "-i"])
+        .arg("testsrc=duration=4:size=320x240:rate=25")
+        .args(["-pix_fmt", "yuv420p"])
+        .arg(&clip)
+        .status()
+        .expect("run ffmpeg");
+    assert!(status.success(), "ffmpeg could not render the test clip");
+    Some(clip)
+}
+
 #[test]
 fn no_args_shows_error() {
     cargo_bin_cmd!("thumbsdown")
@@ -54,3 +83,173 @@ fn output_already_exists_without_force() {
         .failure()
         .stderr(predicate::str::contains("already exists"));
 }
+
+#[test]
+fn zero_jobs_rejected() {
+    cargo_bin_cmd!("thumbsdown")
+        .args(["--jobs", "0", "video.mp4"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--jobs"));
+}
+
+#[test]
+fn multiple_inputs_require_output_template() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    let a = dir.path().join("a.mp4");
+    let b = dir.path().join("b.mp4");
+    std::fs::write(&a, b"fake").expect("write");
+    std::fs::write(&b, b"fake").expect("write");
+
+    cargo_bin_cmd!("thumbsdown")
+        .arg(&a)
+        .arg(&b)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--output template"));
+}
+
+#[test]
+fn empty_directory_has_no_inputs() {
+    let dir = tempfile::tempdir().expect("tempdir");
+
+    cargo_bin_cmd!("thumbsdown")
+        .arg(dir.path())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("no video files"));
+}
+
+#[test]
+fn print_config_merges_config_and_flags() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    let config_dir = dir.path().join("thumbsdown");
+    std::fs::create_dir(&config_dir).expect("mkdir");
+    std::fs::write(
+        config_dir.join("config.toml"),
+        "thumbs = 8\ncolumns = 4\n\n[preset.web]\nformat = \"jpeg\"\n",
+    )
+    .expect("write");
+
+    cargo_bin_cmd!("thumbsdown")
+        .env("XDG_CONFIG_HOME", dir.path())
+        .current_dir(dir.path())
+        .args(["--print-config", "--preset", "web", "-c", "3"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("thumbs = 8"))
+        .stdout(predicate::str::contains("columns = 3"))
+        .stdout(predicate::str::contains("format = \"jpeg\""));
+}
+
+#[test]
+fn captures_generated_clip_in_both_extract_modes() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    let Some(clip) = test_clip(dir.path()) else {
+        return;
+    };
+
+    for mode in ["per-frame", "single-pass"] {
+        let output = dir.path().join(format!("{mode}.png"));
+        let frames = dir.path().join(format!("{mode}-frames"));
+        std::fs::create_dir(&frames).expect("mkdir");
+
+        cargo_bin_cmd!("thumbsdown")
+            .arg(&clip)
+            .args(["-t", "6", "-c", "3", "-w", "160"])
+            .args(["--border-width", "0", "--no-header"])
+            .args(["--extract", mode])
+            .arg("-o")
+            .arg(&output)
+            .arg("-k")
+            .arg(&frames)
+            .assert()
+            .success();
+
+        let sheet = image::open(&output).expect("open sheet");
+        // Two rows of 160x120 thumbnails
+        assert_eq!((sheet.width(), sheet.height()), (480, 240), "{mode}");
+        let kept = std::fs::read_dir(&frames).expect("read frames").count();
+        assert_eq!(kept, 6, "{mode}");
+        let frame = image::open(frames.join("frame-0000.png")).expect("open frame");
+        assert_eq!((frame.width(), frame.height()), (320, 240), "{mode}");
+    }
+}
+
+#[test]
+fn writes_bif_for_generated_clip() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    let Some(clip) = test_clip(dir.path()) else {
+        return;
+    };
+    let bif = dir.path().join("clip.bif");
+
+    cargo_bin_cmd!("thumbsdown")
+        .arg(&clip)
+        .args(["--no-grid", "--bif-interval", "1s", "--bif"])
+        .arg(&bif)
+        .assert()
+        .success();
+
+    let data = std::fs::read(&bif).expect("read bif");
+    assert_eq!(&data[..8], &thumbsdown::bif::MAGIC);
+    let count = u32::from_le_bytes(data[12..16].try_into().expect("4 bytes"));
+    assert_eq!(count, 4);
+}
+
+#[test]
+fn update_tracks_bif_without_grid() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    let Some(clip) = test_clip(dir.path()) else {
+        return;
+    };
+    let bif = dir.path().join("clip.bif");
+    let run = || {
+        cargo_bin_cmd!("thumbsdown")
+            .arg(&clip)
+            .args(["--update", "--no-grid", "--bif-interval", "1s", "--bif"])
+            .arg(&bif)
+            .assert()
+            .success()
+    };
+
+    run();
+    assert!(dir.path().join("clip.bif.thumbsdown.json").exists());
+    run().stderr(predicate::str::contains("Up to date"));
+}
+
+#[test]
+fn update_removes_pages_of_a_longer_sheet() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    let Some(clip) = test_clip(dir.path()) else {
+        return;
+    };
+    let output = dir.path().join("sheet.png");
+    let run = |thumbs: &str| {
+        cargo_bin_cmd!("thumbsdown")
+            .arg(&clip)
+            .args(["--update", "--max-thumbs", "2", "-t", thumbs, "-o"])
+            .arg(&output)
+            .assert()
+            .success();
+    };
+
+    run("6");
+    assert!(dir.path().join("sheet-003.png").exists());
+    run("4");
+    assert!(dir.path().join("sheet-002.png").exists());
+    assert!(!dir.path().join("sheet-003.png").exists());
+}
+
+#[test]
+fn temp_warns_that_it_is_ignored() {
+    let dir = tempfile::tempdir().expect("tempdir");
+
+    cargo_bin_cmd!("thumbsdown")
+        .arg("-T")
+        .arg(dir.path())
+        .arg("nonexistent_video_xyz.mp4")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--temp is deprecated"));
+}